@@ -0,0 +1,80 @@
+use divan::AllocProfiler;
+use std::sync::atomic::AtomicU64;
+use venndb::VennDB;
+
+#[global_allocator]
+static ALLOC: AllocProfiler = AllocProfiler::system();
+
+fn main() {
+    // Run registered benchmarks.
+    divan::main();
+}
+
+const REGIONS: u32 = 100;
+
+#[derive(Debug, Clone, VennDB)]
+struct Node {
+    #[venndb(key)]
+    id: u32,
+    #[venndb(filter)]
+    region: u32,
+    is_active: bool,
+    #[venndb(weight)]
+    capacity: u32,
+}
+
+fn node_db(n: u32) -> NodeDB {
+    NodeDB::from_iter((0..n).map(|id| Node {
+        id,
+        // spread the rows of a region over all blocks of rows
+        region: id % REGIONS,
+        is_active: id % 10 != 0,
+        capacity: id % 7,
+    }))
+    .unwrap()
+}
+
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+fn next_seed() -> u64 {
+    SEED.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
+/// A query matching most of the (weighted) rows,
+/// such that a row is selected out of all rows.
+fn any_weighted_broad(db: &NodeDB) {
+    let mut query = db.query();
+    query.is_active(true);
+    let result = query.execute().unwrap();
+    divan::black_box(result.any_weighted_seeded(next_seed()));
+}
+
+/// A query matching only a small share of the (weighted) rows,
+/// such that a row is selected out of the found rows only.
+fn any_weighted_selective(db: &NodeDB) {
+    let mut query = db.query();
+    query.region((next_seed() % u64::from(REGIONS)) as u32);
+    let result = query.execute().unwrap();
+    divan::black_box(result.any_weighted_seeded(next_seed()));
+}
+
+#[divan::bench(args = [1_000, 100_000])]
+fn venn_any_weighted_broad(bencher: divan::Bencher, n: u32) {
+    bencher
+        .with_inputs(|| node_db(n))
+        .bench_refs(|db| any_weighted_broad(db));
+}
+
+#[divan::bench(args = [1_000, 100_000])]
+fn venn_any_weighted_selective(bencher: divan::Bencher, n: u32) {
+    bencher
+        .with_inputs(|| node_db(n))
+        .bench_refs(|db| any_weighted_selective(db));
+}
+
+#[divan::bench(args = [1_000, 100_000])]
+fn venn_retain_weighted(bencher: divan::Bencher, n: u32) {
+    bencher
+        .with_inputs(|| node_db(n))
+        .bench_refs(|db| db.retain(|node| node.is_active));
+}
@@ -37,14 +37,64 @@ impl BitVec {
         v
     }
 
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn get(&self, index: usize) -> bool {
+        if index >= self.len {
+            return false;
+        }
+        let msk = 1 << (index % BITS_PER_BLOCK);
+        self.data[block_offset(index)] & msk != 0
+    }
+
     #[must_use]
     pub fn iter_ones(&self) -> IterOnes<'_> {
         IterOnes { index: 0, bv: self }
     }
 
+    /// Iterate over the indices of the ones, starting from (and including) the given index.
+    #[must_use]
+    pub fn iter_ones_from(&self, index: usize) -> IterOnes<'_> {
+        IterOnes { index, bv: self }
+    }
+
     #[must_use]
     pub fn count_ones(&self) -> usize {
-        self.iter_ones().count()
+        // bits past the "last" bit are guaranteed to be unset
+        self.data.iter().map(|blk| blk.count_ones() as usize).sum()
+    }
+
+    /// The blocks storing the bits, each holding [`BITS_PER_BLOCK`] bits,
+    /// with no bits set past the "last" bit.
+    #[must_use]
+    pub(crate) fn blocks(&self) -> &[usize] {
+        &self.data
+    }
+
+    /// Return the index of the `k`-th one (zero-based),
+    /// skipping entire blocks using their popcount.
+    #[must_use]
+    pub fn select(&self, k: usize) -> Option<usize> {
+        self.iter_ones().nth(k)
+    }
+
+    /// Count the ones which are set in both `self` and `other`.
+    #[must_use]
+    pub fn count_ones_and(&self, other: &Self) -> usize {
+        self.data
+            .iter()
+            .zip(other.data.iter())
+            .map(|(self_blk, other_blk)| (self_blk & other_blk).count_ones() as usize)
+            .sum()
     }
 
     #[must_use]
@@ -62,7 +112,7 @@ impl BitVec {
         self.set(i, value);
     }
 
-    fn set(&mut self, index: usize, value: bool) -> bool {
+    pub fn set(&mut self, index: usize, value: bool) -> bool {
         if index >= self.len {
             panic!(
                 "Index out of bounds: the len is {} but the index is {}",
@@ -113,6 +163,46 @@ impl BitVec {
         chngd
     }
 
+    /// Clear all bits in `self` which are set in `other`.
+    pub fn and_not(&mut self, other: &Self) -> bool {
+        let mut chngd = false;
+        for (self_blk, other_blk) in self.data.iter_mut().zip(other.data.iter()) {
+            let old_v = *self_blk;
+            let new_v = old_v & !*other_blk;
+            *self_blk = new_v;
+            chngd |= old_v != new_v;
+        }
+        // We don't need to mask the last block as those bits can't be set by "&" by definition.
+        chngd
+    }
+
+    /// Flip all bits in `self`.
+    pub fn negate(&mut self) {
+        for blk in self.data.iter_mut() {
+            *blk = !*blk;
+        }
+        self.mask_last_block();
+    }
+
+    /// Returns `true` if all bits set in `self` are also set in `other`.
+    #[must_use]
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.data
+            .iter()
+            .zip(other.data.iter().chain(std::iter::repeat(&0)))
+            .all(|(self_blk, other_blk)| self_blk & !other_blk == 0)
+    }
+
+    /// Keep only the bits at the indices which are set in `mask`,
+    /// shifting them together such that the length becomes the number of ones in `mask`.
+    pub fn retain(&mut self, mask: &Self) {
+        let mut rv = Self::with_capacity(mask.count_ones());
+        for index in mask.iter_ones() {
+            rv.push(self.get(index));
+        }
+        *self = rv;
+    }
+
     /// We guarantee that the last storage block has no bits set past the "last" bit: this function
     /// clears any such bits.
     fn mask_last_block(&mut self) {
@@ -176,6 +266,52 @@ impl Iterator for IterOnes<'_> {
         }
     }
 
+    fn nth(&mut self, mut n: usize) -> Option<Self::Item> {
+        if self.index >= self.bv.len {
+            return None;
+        }
+
+        let mut b = self.index / BITS_PER_BLOCK;
+        let off = self.index % BITS_PER_BLOCK;
+
+        if b >= self.bv.data.len() {
+            self.index = self.bv.len;
+            return None;
+        }
+
+        let mut v = self.bv.data[b];
+        if off != 0 {
+            v &= usize::MAX << off;
+        }
+
+        // skip entire blocks using their popcount
+        loop {
+            let ones = v.count_ones() as usize;
+            if n < ones {
+                // clear the lowest n set bits
+                for _ in 0..n {
+                    v &= v - 1;
+                }
+                let bit = b * BITS_PER_BLOCK + v.trailing_zeros() as usize;
+                if bit < self.bv.len {
+                    self.index = bit + 1;
+                    return Some(bit);
+                } else {
+                    self.index = self.bv.len;
+                    return None;
+                }
+            }
+            n -= ones;
+
+            b += 1;
+            if b >= self.bv.data.len() {
+                self.index = self.bv.len;
+                return None;
+            }
+            v = self.bv.data[b];
+        }
+    }
+
     fn size_hint(&self) -> (usize, Option<usize>) {
         // cannot know remaining ones cheaply, use a safe upper bound
         let remaining = self.bv.len.saturating_sub(self.index);
@@ -236,8 +372,27 @@ impl std::ops::BitAnd<&Self> for BitVec {
     }
 }
 
+impl std::ops::Not for &BitVec {
+    type Output = BitVec;
+    #[inline(always)]
+    fn not(self) -> BitVec {
+        let mut rv = self.clone();
+        rv.negate();
+        rv
+    }
+}
+
+impl std::ops::Not for BitVec {
+    type Output = Self;
+    #[inline(always)]
+    fn not(mut self) -> Self {
+        self.negate();
+        self
+    }
+}
+
 const BYTES_PER_BLOCK: usize = size_of::<usize>();
-const BITS_PER_BLOCK: usize = BYTES_PER_BLOCK * 8;
+pub(crate) const BITS_PER_BLOCK: usize = BYTES_PER_BLOCK * 8;
 
 #[inline(always)]
 /// Takes as input a number of bits requiring storage; returns an aligned number of blocks needed
@@ -279,6 +434,7 @@ mod tests {
             vob.iter_ones().count(),
             vob.iter_ones().filter(|_| true).count()
         );
+        assert_eq!(vob.count_ones(), vob.iter_ones().count());
         vob
     }
 
@@ -307,4 +463,121 @@ mod tests {
 
         t(&v1, vec![1, 3]);
     }
+
+    #[test]
+    fn test_get_set_and_not() {
+        let mut v = BitVec::repeat(true, 70);
+        assert!(v.set(3, false));
+        assert!(!v.set(3, false));
+        assert!(!v.get(3));
+        assert!(v.get(69));
+        assert!(!v.get(70));
+
+        let mut mask = BitVec::repeat(false, 70);
+        mask.set(0, true);
+        mask.set(65, true);
+        assert!(v.and_not(&mask));
+        assert_eq!(v.count_ones(), 67);
+        assert!(!v.get(0));
+        assert!(!v.get(65));
+    }
+
+    #[test]
+    fn test_iter_ones_nth() {
+        for test_len in [1, 63, 64, 65, 130, 300] {
+            let v = random_bitvec(test_len);
+            let expected: Vec<_> = v.iter_ones().collect();
+            for n in 0..=expected.len() {
+                assert_eq!(v.iter_ones().nth(n), expected.get(n).copied());
+            }
+
+            // successive calls continue where the previous left off
+            let mut iter = v.iter_ones();
+            let mut index = 0;
+            while let Some(bit) = iter.nth(2) {
+                index += 2;
+                assert_eq!(bit, expected[index]);
+                index += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let v = random_bitvec(300);
+        let expected: Vec<_> = v.iter_ones().collect();
+        for (k, bit) in expected.iter().enumerate() {
+            assert_eq!(v.select(k), Some(*bit));
+        }
+        assert_eq!(v.select(expected.len()), None);
+        assert_eq!(BitVec::new().select(0), None);
+        assert_eq!(BitVec::repeat(true, 130).select(129), Some(129));
+    }
+
+    #[test]
+    fn test_iter_ones_from() {
+        let v = random_bitvec(200);
+        let expected: Vec<_> = v.iter_ones().collect();
+        for index in 0..=201 {
+            assert_eq!(
+                v.iter_ones_from(index).collect::<Vec<_>>(),
+                expected
+                    .iter()
+                    .copied()
+                    .filter(|bit| *bit >= index)
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_count_ones_and() {
+        let a = random_bitvec(150);
+        let b = random_bitvec(150);
+        assert_eq!(a.count_ones_and(&b), (&a & &b).count_ones());
+        assert_eq!(a.count_ones_and(&a), a.count_ones());
+        assert_eq!(a.count_ones_and(&BitVec::repeat(false, 150)), 0);
+    }
+
+    #[test]
+    fn test_negate() {
+        let mut v = BitVec::new();
+        for i in 0..70 {
+            v.push(i % 2 == 0);
+        }
+        let w = !&v;
+        assert_eq!(w.len(), 70);
+        assert_eq!(w.count_ones(), 35);
+        assert_eq!(
+            w.iter_ones().collect::<Vec<_>>(),
+            (0..70).filter(|i| i % 2 == 1).collect::<Vec<_>>()
+        );
+        assert_eq!((!BitVec::repeat(false, 70)).count_ones(), 70);
+        assert!(!(!BitVec::repeat(true, 3)).any());
+    }
+
+    #[test]
+    fn test_retain_and_subset() {
+        let mut v = BitVec::new();
+        for i in 0..130 {
+            v.push(i % 3 == 0);
+        }
+
+        let mut mask = BitVec::new();
+        for i in 0..130 {
+            mask.push(i % 2 == 0);
+        }
+
+        let mut w = v.clone();
+        w.retain(&mask);
+        assert_eq!(w.len(), 65);
+        assert_eq!(
+            w.iter_ones().collect::<Vec<_>>(),
+            (0..65).filter(|i| (i * 2) % 3 == 0).collect::<Vec<_>>()
+        );
+
+        assert!(!v.is_subset(&mask));
+        assert!(BitVec::repeat(false, 130).is_subset(&v));
+        assert!(v.is_subset(&BitVec::repeat(true, 130)));
+    }
 }
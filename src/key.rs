@@ -0,0 +1,53 @@
+use std::{
+    borrow::Borrow,
+    hash::{Hash, Hasher},
+};
+
+use hashbrown::Equivalent;
+
+/// A borrowed composite key, used to look up rows by a composite key
+/// (a tuple of owned values) without having to clone its parts.
+///
+/// It hashes the same as the owned tuple, given that a borrowed value
+/// hashes the same as its owned counterpart (as required by [`Borrow`]).
+#[derive(Debug)]
+pub struct KeyRef<T>(pub T);
+
+macro_rules! impl_key_ref {
+    ($(($Q:ident, $K:ident, $index:tt)),+) => {
+        impl<$($Q: Hash + ?Sized,)+> Hash for KeyRef<($(&$Q,)+)> {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                $(self.0.$index.hash(state);)+
+            }
+        }
+
+        impl<$($Q: Eq + ?Sized, $K: Borrow<$Q>,)+> Equivalent<($($K,)+)> for KeyRef<($(&$Q,)+)> {
+            fn equivalent(&self, key: &($($K,)+)) -> bool {
+                $(key.$index.borrow() == self.0.$index)&&+
+            }
+        }
+    };
+}
+
+impl_key_ref!((Q0, K0, 0), (Q1, K1, 1));
+impl_key_ref!((Q0, K0, 0), (Q1, K1, 1), (Q2, K2, 2));
+impl_key_ref!((Q0, K0, 0), (Q1, K1, 1), (Q2, K2, 2), (Q3, K3, 3));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashbrown::HashMap;
+
+    #[test]
+    fn test_key_ref_lookup() {
+        let mut map = HashMap::new();
+        map.insert(("example.com".to_owned(), 443u16), 1);
+        map.insert(("example.com".to_owned(), 80u16), 2);
+
+        assert_eq!(map.get(&KeyRef(("example.com", &443u16))), Some(&1));
+        assert_eq!(map.get(&KeyRef(("example.com", &80u16))), Some(&2));
+        assert_eq!(map.get(&KeyRef(("example.org", &80u16))), None);
+        assert_eq!(map.remove(&KeyRef(("example.com", &80u16))), Some(2));
+        assert!(!map.contains_key(&KeyRef(("example.com", &80u16))));
+    }
+}
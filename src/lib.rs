@@ -63,6 +63,34 @@ impl<T: Any> Any for Box<T> {
 }
 
 mod bitvec;
+mod key;
+mod range;
+mod weight;
+
+/// Expand to the given (generated) items only when the `rand` feature is enabled,
+/// such as the methods making use of the thread-local random number generator.
+#[cfg(feature = "rand")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __with_rand {
+    ($($item:tt)*) => {
+        $($item)*
+    };
+}
+
+/// Expand to the given (generated) items only when the `rand` feature is enabled,
+/// such as the methods making use of the thread-local random number generator.
+#[cfg(not(feature = "rand"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __with_rand {
+    ($($item:tt)*) => {};
+}
+
+mod random;
+#[cfg(feature = "rand")]
+pub use random::RandRng;
+pub use random::{RandomSource, SeededRandom};
 
 #[doc(hidden)]
 pub mod __internal {
@@ -70,15 +98,20 @@ pub mod __internal {
     //! not to be relied upon directly, as they may change at any time.
 
     pub use crate::bitvec::{BitVec, IterOnes};
+    pub use crate::key::KeyRef;
+    pub use crate::range::RangeIndex;
+    pub use crate::weight::WeightIndex;
     pub use hashbrown::HashMap;
-    use rand::Rng;
 
+    #[cfg(feature = "rand")]
     #[must_use]
-    /// Generate a random `usize`.
-    pub fn rand_range(limit: usize) -> usize {
-        rand::rng().random_range(0..limit)
+    /// Return the thread-local random number generator.
+    pub fn rng() -> crate::RandRng<rand::rngs::ThreadRng> {
+        crate::RandRng(rand::rng())
     }
 
+    pub use crate::__with_rand as with_rand;
+
     pub mod hash_map {
         //! Internal types related to hash map.
 
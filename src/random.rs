@@ -0,0 +1,141 @@
+/// A source of randomness, used by the random selection methods
+/// of the query results generated by `#[derive(VennDB)]`, e.g. `any_with`.
+///
+/// It is implemented for [`SeededRandom`], for deterministic (e.g. test) purposes,
+/// and for any [`rand::RngCore`] implementation wrapped in [`RandRng`] when the `rand` feature
+/// is enabled (enabled by default). It can be implemented for your own types
+/// in case you wish to bring your own randomness.
+///
+/// [`rand::RngCore`]: https://docs.rs/rand/latest/rand/trait.RngCore.html
+///
+/// # Example
+///
+/// ```
+/// use venndb::RandomSource;
+///
+/// struct Counter(u64);
+///
+/// impl RandomSource for Counter {
+///     fn random_u64(&mut self) -> u64 {
+///         self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+///         self.0
+///     }
+/// }
+///
+/// let mut rng = Counter(0);
+/// assert!(rng.random_index(10) < 10);
+/// ```
+pub trait RandomSource {
+    /// Returns the next random `u64`, uniformly distributed over all `u64` values.
+    fn random_u64(&mut self) -> u64;
+
+    /// Returns a random index in the range `[0, len)`, without bias.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is zero.
+    fn random_index(&mut self, len: usize) -> usize {
+        assert!(len > 0, "cannot generate a random index for an empty range");
+        // Lemire's nearly divisionless method
+        let range = len as u64;
+        let mut m = self.random_u64() as u128 * range as u128;
+        if (m as u64) < range {
+            let threshold = range.wrapping_neg() % range;
+            while (m as u64) < threshold {
+                m = self.random_u64() as u128 * range as u128;
+            }
+        }
+        (m >> 64) as usize
+    }
+
+    /// Returns a random `f64` in the range `[0, 1)`.
+    fn random_unit(&mut self) -> f64 {
+        (self.random_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
+
+/// A [`RandomSource`] backed by a [`rand`] random number generator,
+/// e.g. `RandRng(rand::rngs::StdRng::seed_from_u64(42))`.
+///
+/// [`rand`]: https://docs.rs/rand
+#[cfg(feature = "rand")]
+#[derive(Debug, Clone, Default)]
+pub struct RandRng<R>(pub R);
+
+#[cfg(feature = "rand")]
+impl<R: rand::RngCore> RandomSource for RandRng<R> {
+    fn random_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+}
+
+/// A small and fast seeded [`RandomSource`] (SplitMix64),
+/// producing the same sequence of numbers for the same seed.
+///
+/// It is not cryptographically secure, and is meant for reproducible
+/// random selections, e.g. in tests and simulations.
+#[derive(Debug, Clone)]
+pub struct SeededRandom {
+    state: u64,
+}
+
+impl SeededRandom {
+    /// Create a new [`SeededRandom`] from the given seed.
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn random_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_random_is_deterministic() {
+        let mut a = SeededRandom::new(42);
+        let mut b = SeededRandom::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.random_u64(), b.random_u64());
+        }
+        assert_ne!(
+            SeededRandom::new(1).random_u64(),
+            SeededRandom::new(2).random_u64()
+        );
+    }
+
+    #[test]
+    fn test_random_index_and_unit() {
+        let mut rng = SeededRandom::new(0);
+        let mut counts = [0; 3];
+        for _ in 0..3000 {
+            counts[rng.random_index(3)] += 1;
+            let unit = rng.random_unit();
+            assert!((0.0..1.0).contains(&unit));
+        }
+        assert!(counts.iter().all(|count| *count > 800), "{counts:?}");
+        assert_eq!(rng.random_index(1), 0);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_rand_rng() {
+        use rand::SeedableRng;
+
+        let mut a = RandRng(rand::rngs::StdRng::seed_from_u64(42));
+        let mut b = RandRng(rand::rngs::StdRng::seed_from_u64(42));
+        for _ in 0..100 {
+            assert_eq!(a.random_index(10), b.random_index(10));
+        }
+    }
+}
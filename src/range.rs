@@ -0,0 +1,296 @@
+use std::ops::Bound;
+
+use crate::bitvec::BitVec;
+
+/// The amount of bins a [`RangeIndex`] aims for.
+const BINS: usize = 64;
+/// The minimum amount of rows per bin, as checking the rows
+/// of small bins one by one is cheaper than maintaining more bitmaps.
+const MIN_BIN_LEN: usize = 64;
+
+/// A range-encoded binned bitmap index over an ordered column,
+/// used to filter rows on a range of values.
+///
+/// The values are divided in bins by a set of (strictly increasing) bounds,
+/// where for each bound a bitmap marks the rows with a value below it.
+/// Rows of the bins fully covered by a range are found using bitmap operations only,
+/// such that only the rows of the (at most two) bins containing the bounds
+/// of the range have to be checked one by one.
+///
+/// The bins are rebuilt from the sorted rows once one of them has doubled in size,
+/// keeping them balanced as rows are appended.
+#[derive(Debug)]
+pub struct RangeIndex<T> {
+    /// The lower bounds of all bins but the first one.
+    bounds: Vec<T>,
+    /// The rows with a value below each bound.
+    below: Vec<BitVec>,
+    /// The rows with a value, as rows without a value are never in range.
+    present: BitVec,
+    /// The amount of rows in each bin, one more than the amount of bounds.
+    counts: Vec<usize>,
+    /// The amount of rows a bin can grow to before the index is rebuilt.
+    max_bin_len: usize,
+}
+
+impl<T> Default for RangeIndex<T> {
+    fn default() -> Self {
+        Self {
+            bounds: Vec::new(),
+            below: Vec::new(),
+            present: BitVec::new(),
+            counts: vec![0],
+            max_bin_len: MIN_BIN_LEN,
+        }
+    }
+}
+
+impl<T: Ord + Clone> RangeIndex<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register all rows appended since the last call,
+    /// given the indices of all rows sorted by their value.
+    pub fn extend<'a, F>(&mut self, sorted: &[usize], value_of: F)
+    where
+        F: Fn(usize) -> Option<&'a T>,
+        T: 'a,
+    {
+        if self.present.is_empty() {
+            self.rebuild(sorted, value_of);
+            return;
+        }
+
+        for index in self.present.len()..sorted.len() {
+            let value = value_of(index);
+            self.present.push(value.is_some());
+            for (bound, below) in self.bounds.iter().zip(self.below.iter_mut()) {
+                below.push(value.is_some_and(|value| value < bound));
+            }
+            if let Some(value) = value {
+                let bin = self.bin(value);
+                self.counts[bin] += 1;
+            }
+        }
+
+        if self.counts.iter().any(|count| *count > self.max_bin_len) {
+            self.rebuild(sorted, value_of);
+        }
+    }
+
+    /// Update the value of an existing row.
+    pub fn update(&mut self, index: usize, old: Option<&T>, new: Option<&T>) {
+        if let Some(old) = old {
+            let bin = self.bin(old);
+            self.counts[bin] -= 1;
+        }
+        self.present.set(index, new.is_some());
+        for (bound, below) in self.bounds.iter().zip(self.below.iter_mut()) {
+            below.set(index, new.is_some_and(|new| new < bound));
+        }
+        if let Some(new) = new {
+            let bin = self.bin(new);
+            self.counts[bin] += 1;
+        }
+    }
+
+    /// Rebuild the index for all rows, given their indices sorted by their value.
+    pub fn rebuild<'a, F>(&mut self, sorted: &[usize], value_of: F)
+    where
+        F: Fn(usize) -> Option<&'a T>,
+        T: 'a,
+    {
+        let len = sorted.len();
+        // rows without a value are skipped, these are never in range
+        let values: Vec<_> = sorted
+            .iter()
+            .filter_map(|index| value_of(*index).map(|value| (*index, value)))
+            .collect();
+        let bin_len = usize::max(MIN_BIN_LEN, values.len().div_ceil(BINS));
+
+        self.bounds.clear();
+        self.below.clear();
+        self.counts.clear();
+        let mut current = BitVec::repeat(false, len);
+        let mut count = 0;
+        for (rank, (index, value)) in values.iter().enumerate() {
+            // equal values always end up in the same bin
+            if count >= bin_len && values[rank - 1].1 < *value {
+                self.bounds.push((*value).clone());
+                self.below.push(current.clone());
+                self.counts.push(count);
+                count = 0;
+            }
+            current.set(*index, true);
+            count += 1;
+        }
+        self.counts.push(count);
+        self.present = current;
+        // bins of equal values can be larger than aimed for,
+        // these only trigger a rebuild again once doubled in size
+        let largest_bin_len = self.counts.iter().copied().max().unwrap_or_default();
+        self.max_bin_len = 2 * usize::max(bin_len, largest_bin_len);
+    }
+
+    /// Return the rows with a value within the given bounds.
+    pub fn range<'a, F>(&self, bounds: (Bound<&T>, Bound<&T>), value_of: F) -> BitVec
+    where
+        F: Fn(usize) -> Option<&'a T>,
+        T: 'a,
+    {
+        let mut rows = match bounds.1 {
+            Bound::Included(end) => self.below(end, true, &value_of),
+            Bound::Excluded(end) => self.below(end, false, &value_of),
+            Bound::Unbounded => self.present.clone(),
+        };
+        match bounds.0 {
+            Bound::Included(start) => {
+                rows.and_not(&self.below(start, false, &value_of));
+            }
+            Bound::Excluded(start) => {
+                rows.and_not(&self.below(start, true, &value_of));
+            }
+            Bound::Unbounded => (),
+        }
+        rows
+    }
+
+    /// The bin in which the given value is (or would be) stored.
+    fn bin(&self, value: &T) -> usize {
+        self.bounds.partition_point(|bound| bound <= value)
+    }
+
+    /// Return the rows with a value below (or equal to, if `inclusive`) the given value.
+    fn below<'a, F>(&self, value: &T, inclusive: bool, value_of: &F) -> BitVec
+    where
+        F: Fn(usize) -> Option<&'a T>,
+        T: 'a,
+    {
+        let bin = self.bin(value);
+        // all rows of the bins before the bin of the value are below it
+        let mut rows = match bin {
+            0 => BitVec::repeat(false, self.present.len()),
+            bin => self.below[bin - 1].clone(),
+        };
+        if !inclusive && bin > 0 && self.bounds[bin - 1] == *value {
+            // none of the rows of the bin are below its lower bound
+            return rows;
+        }
+
+        // only the rows of the bin of the value itself have to be checked one by one
+        let mut edge = match self.below.get(bin) {
+            Some(below) => below.clone(),
+            None => self.present.clone(),
+        };
+        if bin > 0 {
+            edge.and_not(&self.below[bin - 1]);
+        }
+        for index in edge.iter_ones() {
+            let is_below = value_of(index).is_some_and(|other| {
+                if inclusive {
+                    other <= value
+                } else {
+                    other < value
+                }
+            });
+            if is_below {
+                rows.set(index, true);
+            }
+        }
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(values: &[Option<u32>]) -> Vec<usize> {
+        let mut sorted: Vec<_> = (0..values.len()).collect();
+        sorted.sort_by_key(|index| values[*index]);
+        sorted
+    }
+
+    fn assert_range(
+        index: &RangeIndex<u32>,
+        values: &[Option<u32>],
+        bounds: (Bound<u32>, Bound<u32>),
+    ) {
+        let rows = index.range((bounds.0.as_ref(), bounds.1.as_ref()), |i| {
+            values[i].as_ref()
+        });
+        let expected: Vec<_> = (0..values.len())
+            .filter(|i| {
+                values[*i].is_some_and(|value| {
+                    std::ops::RangeBounds::contains(&(bounds.0, bounds.1), &value)
+                })
+            })
+            .collect();
+        assert_eq!(rows.iter_ones().collect::<Vec<_>>(), expected, "{bounds:?}");
+    }
+
+    #[test]
+    fn test_range_index() {
+        // enough rows for multiple bins, including duplicate and missing values
+        let mut values: Vec<_> = (0..1000u32)
+            .map(|i| (!i.is_multiple_of(7)).then_some((i * 7919) % 500))
+            .collect();
+
+        let mut index = RangeIndex::new();
+        index.extend(&sorted(&values[..10]), |i| values[i].as_ref());
+        for len in 11..=values.len() {
+            index.extend(&sorted(&values[..len]), |i| values[i].as_ref());
+        }
+        assert!(index.bounds.len() > 1);
+
+        for (start, end) in [
+            (0, 0),
+            (0, 499),
+            (100, 200),
+            (250, 251),
+            (498, 600),
+            (42, 41),
+        ] {
+            assert_range(
+                &index,
+                &values,
+                (Bound::Included(start), Bound::Included(end)),
+            );
+            assert_range(
+                &index,
+                &values,
+                (Bound::Excluded(start), Bound::Excluded(end)),
+            );
+            assert_range(&index, &values, (Bound::Included(start), Bound::Unbounded));
+            assert_range(&index, &values, (Bound::Unbounded, Bound::Excluded(end)));
+        }
+        for bound in index.bounds.clone() {
+            assert_range(
+                &index,
+                &values,
+                (Bound::Excluded(bound), Bound::Excluded(bound + 10)),
+            );
+            assert_range(
+                &index,
+                &values,
+                (Bound::Included(bound), Bound::Excluded(bound)),
+            );
+        }
+
+        // updated values move between bins
+        index.update(3, values[3].as_ref(), Some(&1000));
+        values[3] = Some(1000);
+        index.update(14, values[14].as_ref(), Some(&0));
+        values[14] = Some(0);
+        index.update(1, values[1].as_ref(), None);
+        values[1] = None;
+        assert_range(&index, &values, (Bound::Included(0), Bound::Included(0)));
+        assert_range(&index, &values, (Bound::Excluded(400), Bound::Unbounded));
+        assert_range(&index, &values, (Bound::Unbounded, Bound::Unbounded));
+
+        index.rebuild(&sorted(&values), |i| values[i].as_ref());
+        assert_range(&index, &values, (Bound::Included(10), Bound::Included(490)));
+    }
+}
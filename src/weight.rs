@@ -0,0 +1,380 @@
+use crate::bitvec::{BITS_PER_BLOCK, BitVec};
+
+/// The weights of all rows, used to select rows proportionally to their weight.
+///
+/// The weights are stored in a Fenwick (binary indexed) tree, such that
+/// both updating the weight of a single row and selecting a row by
+/// its cumulative weight take `O(log n)`, rather than `O(n)` for
+/// maintaining a plain list of cumulative weights.
+///
+/// The sum of the weights is also kept per block of rows, aligned with the blocks
+/// of a [`BitVec`], such that a row can be selected out of the rows of a bitmap
+/// without having to sum the weights of the blocks of which all rows are set.
+///
+/// Only positive and finite weights are stored, all other weights
+/// (zero, negative, NaN or infinite) are treated as zero, meaning
+/// that such rows are never selected.
+#[derive(Debug, Default)]
+pub struct WeightIndex {
+    /// The (sanitized) weight of each row.
+    weights: Vec<f64>,
+    /// The Fenwick tree over `weights`, where the node of (one-based) position `i`
+    /// holds the sum of the weights of the rows in `(i - lowbit(i), i]`.
+    tree: Vec<f64>,
+    /// The sum of the weights of the rows of each block of [`BITS_PER_BLOCK`] rows.
+    blocks: Vec<f64>,
+}
+
+impl WeightIndex {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            weights: Vec::with_capacity(capacity),
+            tree: Vec::with_capacity(capacity),
+            blocks: Vec::with_capacity(capacity.div_ceil(BITS_PER_BLOCK)),
+        }
+    }
+
+    /// The amount of rows in the index.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    /// The weight of the row at the given index.
+    #[must_use]
+    pub fn get(&self, index: usize) -> f64 {
+        self.weights.get(index).copied().unwrap_or_default()
+    }
+
+    /// Append the weight of a new row.
+    pub fn push(&mut self, weight: f64) {
+        let weight = sanitize(weight);
+        if self.weights.len().is_multiple_of(BITS_PER_BLOCK) {
+            self.blocks.push(0.0);
+        }
+        self.weights.push(weight);
+        *self.blocks.last_mut().unwrap() += weight;
+        // the node of the new position sums the weight and the nodes of its children
+        let position = self.weights.len();
+        let start = position - lowbit(position);
+        let mut sum = weight;
+        let mut child = position - 1;
+        while child > start {
+            sum += self.tree[child - 1];
+            child -= lowbit(child);
+        }
+        self.tree.push(sum);
+    }
+
+    /// Update the weight of an existing row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn set(&mut self, index: usize, weight: f64) {
+        let weight = sanitize(weight);
+        let delta = weight - self.weights[index];
+        if delta == 0.0 {
+            return;
+        }
+        self.weights[index] = weight;
+        // summed again rather than applying the delta, to not accumulate rounding errors
+        let block = index / BITS_PER_BLOCK;
+        self.blocks[block] = self.block_rows(block).iter().sum();
+        let mut position = index + 1;
+        while position <= self.tree.len() {
+            self.tree[position - 1] += delta;
+            position += lowbit(position);
+        }
+    }
+
+    /// Rebuild the index for the given weights of all rows, in `O(n)`.
+    ///
+    /// This also gets rid of any rounding errors accumulated by updating weights.
+    pub fn rebuild(&mut self, weights: impl IntoIterator<Item = f64>) {
+        self.weights.clear();
+        self.weights.extend(weights.into_iter().map(sanitize));
+        self.blocks.clear();
+        self.blocks.extend(
+            self.weights
+                .chunks(BITS_PER_BLOCK)
+                .map(|block| block.iter().sum::<f64>()),
+        );
+        self.tree.clear();
+        self.tree.extend_from_slice(&self.weights);
+        for position in 1..=self.tree.len() {
+            let parent = position + lowbit(position);
+            if parent <= self.tree.len() {
+                self.tree[parent - 1] += self.tree[position - 1];
+            }
+        }
+    }
+
+    /// The sum of the weights of all rows.
+    #[must_use]
+    pub fn total(&self) -> f64 {
+        let mut sum = 0.0;
+        let mut position = self.tree.len();
+        while position > 0 {
+            sum += self.tree[position - 1];
+            position -= lowbit(position);
+        }
+        sum
+    }
+
+    /// Return the index of the row at the given cumulative weight,
+    /// being the first row for which the sum of the weights up to
+    /// and including the row exceeds the target.
+    ///
+    /// Returns `None` in case the target is not below the total weight,
+    /// or if the found row has no weight due to rounding errors.
+    #[must_use]
+    pub fn find(&self, target: f64) -> Option<usize> {
+        let len = self.tree.len();
+        if len == 0 {
+            return None;
+        }
+        let mut position = 0;
+        let mut remaining = target;
+        let mut step = 1 << len.ilog2();
+        while step > 0 {
+            let next = position + step;
+            if next <= len && self.tree[next - 1] <= remaining {
+                position = next;
+                remaining -= self.tree[next - 1];
+            }
+            step >>= 1;
+        }
+        (position < len && self.weights[position] > 0.0).then_some(position)
+    }
+
+    /// Select a row out of the rows set in the given bitmap,
+    /// proportionally to their weight, given a random number in `[0, 1)`.
+    ///
+    /// This takes `O(n / 64 + k)`, where `k` is the amount of rows set in the bitmap
+    /// for the blocks of which not all rows are set, as the weights of blocks
+    /// of which all rows are set are summed already.
+    ///
+    /// Returns `None` in case all rows of the bitmap have no weight,
+    /// or if the sum of their weights is not finite.
+    #[must_use]
+    pub fn select_ones(&self, rows: &BitVec, unit: f64) -> Option<usize> {
+        let blocks = || {
+            rows.blocks()
+                .iter()
+                .copied()
+                .enumerate()
+                .take(self.blocks.len())
+                .filter(|(_, bits)| *bits != 0)
+        };
+
+        let total: f64 = blocks()
+            .map(|(block, bits)| self.block_weight(block, bits))
+            .sum();
+        if total <= 0.0 || !total.is_finite() {
+            return None;
+        }
+        let mut target = unit * total;
+        let mut last = None;
+        for (block, bits) in blocks() {
+            let weight = self.block_weight(block, bits);
+            if weight <= 0.0 {
+                continue;
+            }
+            if target < weight {
+                return self.select(block_ones(block, bits), target);
+            }
+            target -= weight;
+            last = Some((block, bits));
+        }
+        // guard against rounding errors
+        last.and_then(|(block, bits)| self.select(block_ones(block, bits), f64::INFINITY))
+    }
+
+    /// Select a row out of the given rows, proportionally to their weight,
+    /// given a random number in `[0, 1)`, in `O(k)`.
+    ///
+    /// Returns `None` in case all given rows have no weight,
+    /// or if the sum of their weights is not finite.
+    #[must_use]
+    pub fn select_indices(&self, rows: &[usize], unit: f64) -> Option<usize> {
+        let total: f64 = rows.iter().map(|index| self.get(*index)).sum();
+        if total <= 0.0 || !total.is_finite() {
+            return None;
+        }
+        self.select(rows.iter().copied(), unit * total)
+    }
+
+    /// Select the row at the given cumulative weight out of the given rows,
+    /// returning the last row with a weight in case the target is out of range.
+    fn select(&self, rows: impl Iterator<Item = usize>, mut target: f64) -> Option<usize> {
+        let mut last = None;
+        for index in rows {
+            let weight = self.get(index);
+            if weight > 0.0 {
+                if target < weight {
+                    return Some(index);
+                }
+                target -= weight;
+                last = Some(index);
+            }
+        }
+        last
+    }
+
+    /// The weights of the rows of the given block.
+    fn block_rows(&self, block: usize) -> &[f64] {
+        let start = block * BITS_PER_BLOCK;
+        let end = usize::min(start + BITS_PER_BLOCK, self.weights.len());
+        &self.weights[start..end]
+    }
+
+    /// The sum of the weights of the rows set in the given bits of a block.
+    fn block_weight(&self, block: usize, bits: usize) -> f64 {
+        let rows = self.block_rows(block);
+        if bits.count_ones() as usize == rows.len() {
+            self.blocks[block]
+        } else {
+            block_ones(block, bits)
+                .map(|index| self.weights[index])
+                .sum()
+        }
+    }
+}
+
+/// The indices of the rows set in the given bits of a block.
+fn block_ones(block: usize, mut bits: usize) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+        let bit = bits.trailing_zeros() as usize;
+        bits &= bits - 1;
+        Some(block * BITS_PER_BLOCK + bit)
+    })
+}
+
+/// Treat all weights which are not positive and finite as zero.
+fn sanitize(weight: f64) -> f64 {
+    if weight > 0.0 && weight.is_finite() {
+        weight
+    } else {
+        0.0
+    }
+}
+
+/// The lowest set bit of the given (one-based) position.
+const fn lowbit(position: usize) -> usize {
+    position & position.wrapping_neg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_index(index: &WeightIndex, weights: &[f64]) {
+        assert_eq!(index.len(), weights.len());
+        let mut sum = 0.0;
+        for (i, weight) in weights.iter().enumerate() {
+            assert_eq!(index.get(i), *weight);
+            if *weight > 0.0 {
+                // the first and (almost) last cumulative weight within the row select it
+                assert_eq!(index.find(sum), Some(i), "{weights:?}");
+                assert_eq!(index.find(sum + weight * 0.99), Some(i), "{weights:?}");
+            }
+            sum += weight;
+        }
+        assert_eq!(index.total(), sum);
+        assert_eq!(index.find(sum), None);
+
+        // the weights of the rows of each block are summed
+        let mut rebuilt = WeightIndex::new();
+        rebuilt.rebuild(weights.iter().copied());
+        assert_eq!(index.blocks, rebuilt.blocks);
+    }
+
+    /// Select rows out of the given rows for a range of random numbers,
+    /// checking that these are selected proportionally to their weight.
+    fn assert_select(index: &WeightIndex, weights: &[f64], rows: &[usize]) {
+        let mut bitmap = BitVec::repeat(false, weights.len());
+        for row in rows {
+            bitmap.set(*row, true);
+        }
+        let total: f64 = rows.iter().map(|row| weights[*row]).sum();
+        if total == 0.0 {
+            assert_eq!(index.select_ones(&bitmap, 0.5), None);
+            assert_eq!(index.select_indices(rows, 0.5), None);
+            return;
+        }
+
+        let mut sum = 0.0;
+        for row in rows {
+            let weight = weights[*row];
+            if weight > 0.0 {
+                let unit = (sum + weight / 2.0) / total;
+                assert_eq!(index.select_ones(&bitmap, unit), Some(*row), "{rows:?}");
+                assert_eq!(index.select_indices(rows, unit), Some(*row), "{rows:?}");
+            }
+            sum += weight;
+        }
+    }
+
+    #[test]
+    fn test_weight_index() {
+        let mut weights: Vec<_> = (0..100u32).map(|i| f64::from(i % 7)).collect();
+
+        let mut index = WeightIndex::new();
+        assert_eq!(index.total(), 0.0);
+        assert_eq!(index.find(0.0), None);
+        for weight in &weights {
+            index.push(*weight);
+        }
+        assert_index(&index, &weights);
+
+        let mut rebuilt = WeightIndex::new();
+        rebuilt.rebuild(weights.iter().copied());
+        assert_eq!(rebuilt.tree, index.tree);
+
+        for (i, weight) in [(0, 3.0), (6, 0.0), (63, 10.0), (64, 1.0), (99, 0.0)] {
+            index.set(i, weight);
+            weights[i] = weight;
+        }
+        assert_index(&index, &weights);
+
+        index.push(2.0);
+        weights.push(2.0);
+        assert_index(&index, &weights);
+
+        let all: Vec<_> = (0..weights.len()).collect();
+        assert_select(&index, &weights, &all);
+        assert_select(&index, &weights, &[3, 64, 65, 100]);
+        assert_select(&index, &weights, &[0, 6, 7, 8]);
+        assert_select(&index, &weights, &[6, 99]);
+        let partial: Vec<_> = (0..weights.len()).filter(|i| i % 3 != 0).collect();
+        assert_select(&index, &weights, &partial);
+    }
+
+    #[test]
+    fn test_weight_index_sanitize() {
+        let mut index = WeightIndex::new();
+        for weight in [-1.0, f64::NAN, f64::INFINITY, 0.0, 1.0] {
+            index.push(weight);
+        }
+        assert_index(&index, &[0.0, 0.0, 0.0, 0.0, 1.0]);
+
+        index.set(4, f64::NEG_INFINITY);
+        assert_eq!(index.total(), 0.0);
+        assert_eq!(index.find(0.0), None);
+    }
+}
@@ -2,9 +2,10 @@
 
 use crate::{
     errors::Errors,
-    parse_attrs::{FieldAttrs, FieldKind},
+    parse_attrs::{ComputedFilterAttr, FieldAttrs, FieldKind, is_bool, ty_inner},
 };
-use quote::format_ident;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
 use syn::Ident;
 
 /// A field of a `#![derive(VennDB)]` struct with attributes and some other
@@ -25,26 +26,155 @@ pub enum FieldInfo<'a> {
     Key(KeyField<'a>),
     Filter(FilterField<'a>),
     FilterMap(FilterMapField<'a>),
+    Sort(SortField<'a>),
+    Index(IndexField<'a>),
 }
 
+impl<'a> FieldInfo<'a> {
+    /// Create the info of a filter (map) computed from the row,
+    /// typed the same way as a struct field marked with `#[venndb(filter)]`.
+    pub fn computed(filter: &'a ComputedFilterAttr) -> Self {
+        let Some(ty) = &filter.ty else {
+            return FieldInfo::Filter(FilterField {
+                name: &filter.name,
+                optional: false,
+                computed: Some(&filter.with),
+            });
+        };
+        let option_ty = ty_inner(&["Option"], ty);
+        if is_bool(option_ty.unwrap_or(ty)) {
+            return FieldInfo::Filter(FilterField {
+                name: &filter.name,
+                optional: option_ty.is_some(),
+                computed: Some(&filter.with),
+            });
+        }
+        let collection_ty = filter
+            .multi
+            .then(|| ty_inner(&["Vec", "HashSet", "BTreeSet"], ty))
+            .flatten();
+        FieldInfo::FilterMap(FilterMapField {
+            name: &filter.name,
+            ty: collection_ty.or(option_ty).unwrap_or(ty),
+            optional: option_ty.is_some(),
+            any: false,
+            multi: collection_ty.is_some(),
+            computed: Some(&filter.with),
+        })
+    }
+
+    /// Return the names of the setters generated on the query for this field,
+    /// which are also the names of the fields of the query holding their values.
+    pub fn query_names(&self) -> Vec<Ident> {
+        match self {
+            FieldInfo::Filter(field) => {
+                let mut names = vec![field.name.clone()];
+                if field.optional {
+                    names.extend([field.is_some_name(), field.is_none_name()]);
+                }
+                names
+            }
+            FieldInfo::FilterMap(field) => {
+                let mut names = vec![field.name.clone(), field.not_name()];
+                names.extend(field.all_of_name());
+                if field.optional {
+                    names.extend([field.is_some_name(), field.is_none_name()]);
+                }
+                names
+            }
+            FieldInfo::Sort(field) => match field.range_name() {
+                Some(range_name) => vec![
+                    range_name,
+                    field.between_name(),
+                    field.gt_name(),
+                    field.lt_name(),
+                ],
+                None => Vec::new(),
+            },
+            FieldInfo::Key(_) | FieldInfo::Index(_) => Vec::new(),
+        }
+    }
+
+    /// Bind the value of a computed filter (map) for the row being indexed (`data`),
+    /// such that it is only computed once.
+    pub fn computed_binding(&self) -> Option<TokenStream> {
+        match self {
+            FieldInfo::Filter(field) => computed_binding(field.name, field.computed),
+            FieldInfo::FilterMap(field) => computed_binding(field.name, field.computed),
+            FieldInfo::Key(_) | FieldInfo::Sort(_) | FieldInfo::Index(_) => None,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct KeyField<'a> {
+    name: Ident,
+    parts: Vec<KeyPart<'a>>,
+    /// Rows without a value (`None`) are not registered for optional keys,
+    /// only single field keys can be optional.
+    pub optional: bool,
+}
+
+/// A field which is (part of) a key.
+#[derive(Clone)]
+pub struct KeyPart<'a> {
     pub name: &'a Ident,
     pub ty: &'a syn::Type,
 }
 
 impl<'a> KeyField<'a> {
-    pub fn name(&'a self) -> &'a Ident {
-        self.name
+    /// Create a key consisting of a single field.
+    pub fn new(name: &'a Ident, ty: &'a syn::Type, optional: bool) -> Self {
+        Self {
+            name: name.clone(),
+            parts: vec![KeyPart { name, ty }],
+            optional,
+        }
     }
 
-    pub fn ty(&'a self) -> &'a syn::Type {
-        self.ty
+    /// Create a key consisting of multiple fields,
+    /// named after all its fields (e.g. `host_port`).
+    pub fn composite(parts: Vec<KeyPart<'a>>) -> Self {
+        let name = parts
+            .iter()
+            .map(|part| part.name.to_string())
+            .collect::<Vec<_>>()
+            .join("_");
+        Self {
+            name: format_ident!("{}", name),
+            parts,
+            optional: false,
+        }
+    }
+
+    pub fn name(&self) -> &Ident {
+        &self.name
+    }
+
+    pub fn parts(&self) -> &[KeyPart<'a>] {
+        &self.parts
+    }
+
+    pub fn is_composite(&self) -> bool {
+        self.parts.len() > 1
     }
 
     pub fn method_name(&self) -> Ident {
         format_ident!("get_by_{}", self.name)
     }
 
+    pub fn get_mut_method_name(&self) -> Ident {
+        format_ident!("get_mut_by_{}", self.name)
+    }
+
+    pub fn update_method_name(&self) -> Ident {
+        format_ident!("update_by_{}", self.name)
+    }
+
+    pub fn remove_method_name(&self) -> Ident {
+        format_ident!("remove_by_{}", self.name)
+    }
+
     pub fn map_name(&self) -> Ident {
         format_ident!("map_{}", self.name)
     }
@@ -53,6 +183,9 @@ impl<'a> KeyField<'a> {
 pub struct FilterField<'a> {
     pub name: &'a Ident,
     pub optional: bool,
+    /// The function computing the filter from the row,
+    /// in case it is not a field of the row.
+    pub computed: Option<&'a syn::Path>,
 }
 
 impl<'a> FilterField<'a> {
@@ -64,9 +197,25 @@ impl<'a> FilterField<'a> {
         format_ident!("filter_{}", self.name)
     }
 
+    pub fn value(&self) -> TokenStream {
+        filter_value(self.name, self.computed)
+    }
+
     pub fn filter_not_name(&self) -> Ident {
         format_ident!("filter_not_{}", self.name)
     }
+
+    pub fn filter_some_name(&self) -> Option<Ident> {
+        filter_some_name(self.name, self.optional)
+    }
+
+    pub fn is_some_name(&self) -> Ident {
+        format_ident!("{}_is_some", self.name)
+    }
+
+    pub fn is_none_name(&self) -> Ident {
+        format_ident!("{}_is_none", self.name)
+    }
 }
 
 impl<'a> StructField<'a> {
@@ -77,32 +226,97 @@ impl<'a> StructField<'a> {
         Some(StructField { field, attrs, name })
     }
 
+    /// Stop indexing this struct field, as if it was marked with `#[venndb(skip)]`.
+    pub fn skip(&mut self) {
+        self.attrs.kind = None;
+    }
+
+    /// Return the name of this struct field.
+    pub fn name(&self) -> &'a Ident {
+        self.name
+    }
+
+    /// Return the (original) type of this struct field.
+    pub fn ty(&self) -> &'a syn::Type {
+        &self.field.ty
+    }
+
+    /// Return `true` if this struct field is optional (`Option<T>`).
+    pub fn is_optional(&self) -> bool {
+        self.attrs.option_ty.is_some()
+    }
+
+    /// Return the visibility of this struct field.
+    pub fn vis(&self) -> &'a syn::Visibility {
+        &self.field.vis
+    }
+
     /// Return the method name for this struct field.
     pub fn info(&self) -> Option<FieldInfo<'_>> {
-        self.attrs.kind.as_ref().map(|kind| match kind {
-            FieldKind::Key => FieldInfo::Key(KeyField {
+        self.attrs.kind.as_ref().and_then(|kind| match kind {
+            FieldKind::Key => Some(FieldInfo::Key(KeyField::new(
+                self.name,
+                self.attrs.option_ty.unwrap_or(&self.field.ty),
+                self.attrs.option_ty.is_some(),
+            ))),
+            FieldKind::Filter => Some(FieldInfo::Filter(FilterField {
                 name: self.name,
-                ty: self.attrs.option_ty.unwrap_or(&self.field.ty),
-            }),
-            FieldKind::Filter => FieldInfo::Filter(FilterField {
+                optional: self.attrs.option_ty.is_some(),
+                computed: None,
+            })),
+            FieldKind::FilterMap { any } => Some(FieldInfo::FilterMap(FilterMapField {
                 name: self.name,
+                ty: self
+                    .attrs
+                    .collection_ty
+                    .or(self.attrs.option_ty)
+                    .unwrap_or(&self.field.ty),
                 optional: self.attrs.option_ty.is_some(),
-            }),
-            FieldKind::FilterMap { any } => FieldInfo::FilterMap(FilterMapField {
+                any: *any,
+                multi: self.attrs.collection_ty.is_some(),
+                computed: None,
+            })),
+            FieldKind::Sort { range } => Some(FieldInfo::Sort(SortField {
                 name: self.name,
                 ty: self.attrs.option_ty.unwrap_or(&self.field.ty),
                 optional: self.attrs.option_ty.is_some(),
-                any: *any,
-            }),
+                range: *range,
+            })),
+            FieldKind::Index => Some(FieldInfo::Index(IndexField {
+                name: self.name,
+                ty: self.attrs.option_ty.unwrap_or(&self.field.ty),
+                optional: self.attrs.option_ty.is_some(),
+            })),
+            // weights are not indexed, see `Self::weight`
+            FieldKind::Weight => None,
         })
     }
+
+    /// Return the weight info for this struct field,
+    /// in case it is marked as the `weight` of the row.
+    pub fn weight(&self) -> Option<WeightField<'_>> {
+        match self.attrs.kind {
+            Some(FieldKind::Weight) => Some(WeightField {
+                name: self.name,
+                optional: self.attrs.option_ty.is_some(),
+            }),
+            _ => None,
+        }
+    }
 }
 
 pub struct FilterMapField<'a> {
     pub name: &'a Ident,
+    /// The type of the values, i.e. the element type in case of a collection.
     pub ty: &'a syn::Type,
     pub optional: bool,
     pub any: bool,
+    /// Whether the field is a collection (e.g. `Vec<T>`) of values,
+    /// with the row registered for each of them.
+    pub multi: bool,
+    /// The function computing the filter map value from the row,
+    /// in case it is not a field of the row.
+    pub computed: Option<&'a syn::Path>,
 }
 
 impl<'a> FilterMapField<'a> {
@@ -118,6 +332,10 @@ impl<'a> FilterMapField<'a> {
         format_ident!("filter_map_{}", self.name)
     }
 
+    pub fn value(&self) -> TokenStream {
+        filter_value(self.name, self.computed)
+    }
+
     pub fn filter_vec_name(&self) -> Ident {
         format_ident!("filter_vec_{}", self.name)
     }
@@ -129,4 +347,186 @@ impl<'a> FilterMapField<'a> {
             None
         }
     }
+
+    pub fn not_name(&self) -> Ident {
+        format_ident!("not_{}", self.name)
+    }
+
+    pub fn all_of_name(&self) -> Option<Ident> {
+        if self.multi {
+            Some(format_ident!("all_of_{}", self.name))
+        } else {
+            None
+        }
+    }
+
+    pub fn distinct_name(&self) -> Ident {
+        format_ident!("distinct_{}", self.name)
+    }
+
+    pub fn has_any_name(&self) -> Ident {
+        format_ident!("has_any_{}", self.name)
+    }
+
+    pub fn group_by_name(&self) -> Ident {
+        format_ident!("group_by_{}", self.name)
+    }
+
+    pub fn facets_name(&self) -> Ident {
+        format_ident!("facets_{}", self.name)
+    }
+
+    pub fn facets_within_name(&self) -> Ident {
+        format_ident!("facets_{}_within", self.name)
+    }
+
+    pub fn filter_some_name(&self) -> Option<Ident> {
+        filter_some_name(self.name, self.optional)
+    }
+
+    pub fn is_some_name(&self) -> Ident {
+        format_ident!("{}_is_some", self.name)
+    }
+
+    pub fn is_none_name(&self) -> Ident {
+        format_ident!("{}_is_none", self.name)
+    }
+}
+
+pub struct SortField<'a> {
+    pub name: &'a Ident,
+    pub ty: &'a syn::Type,
+    pub optional: bool,
+    pub range: bool,
+}
+
+impl<'a> SortField<'a> {
+    pub fn name(&'a self) -> &'a Ident {
+        self.name
+    }
+
+    pub fn ty(&'a self) -> &'a syn::Type {
+        self.ty
+    }
+
+    pub fn sort_name(&self) -> Ident {
+        format_ident!("sort_{}", self.name)
+    }
+
+    pub fn sorted_by_name(&self) -> Ident {
+        format_ident!("sorted_by_{}", self.name)
+    }
+
+    pub fn range_name(&self) -> Option<Ident> {
+        if self.range {
+            Some(format_ident!("range_{}", self.name))
+        } else {
+            None
+        }
+    }
+
+    pub fn range_index_name(&self) -> Option<Ident> {
+        if self.range {
+            Some(format_ident!("range_index_{}", self.name))
+        } else {
+            None
+        }
+    }
+
+    /// The value of this field for the given row, as an `Option<&T>`.
+    pub fn range_value(&self, row: TokenStream) -> TokenStream {
+        let name = self.name;
+        if self.optional {
+            quote! { #row.#name.as_ref() }
+        } else {
+            quote! { Some(&#row.#name) }
+        }
+    }
+
+    pub fn between_name(&self) -> Ident {
+        format_ident!("{}_between", self.name)
+    }
+
+    pub fn gt_name(&self) -> Ident {
+        format_ident!("{}_gt", self.name)
+    }
+
+    pub fn lt_name(&self) -> Ident {
+        format_ident!("{}_lt", self.name)
+    }
+}
+
+pub struct IndexField<'a> {
+    pub name: &'a Ident,
+    pub ty: &'a syn::Type,
+    pub optional: bool,
+}
+
+impl<'a> IndexField<'a> {
+    pub fn name(&'a self) -> &'a Ident {
+        self.name
+    }
+
+    pub fn ty(&'a self) -> &'a syn::Type {
+        self.ty
+    }
+
+    pub fn index_name(&self) -> Ident {
+        format_ident!("index_{}", self.name)
+    }
+
+    pub fn get_all_by_name(&self) -> Ident {
+        format_ident!("get_all_by_{}", self.name)
+    }
+}
+
+pub struct WeightField<'a> {
+    pub name: &'a Ident,
+    pub optional: bool,
+}
+
+impl<'a> WeightField<'a> {
+    pub fn name(&'a self) -> &'a Ident {
+        self.name
+    }
+
+    /// The weight of the given row as a `f64`, where
+    /// missing, negative and NaN weights are treated as zero.
+    pub fn value(&self, row: TokenStream) -> TokenStream {
+        let name = self.name;
+        if self.optional {
+            quote! { f64::max(#row.#name.map(|weight| weight as f64).unwrap_or_default(), 0.0) }
+        } else {
+            quote! { f64::max(#row.#name as f64, 0.0) }
+        }
+    }
+}
+
+/// The name of the presence column of an optional filter (map),
+/// tracking which rows have a value (`Some`) for that field.
+fn filter_some_name(name: &Ident, optional: bool) -> Option<Ident> {
+    if optional {
+        Some(format_ident!("filter_some_{}", name))
+    } else {
+        None
+    }
+}
+
+/// The value of a filter (map) for the row being indexed (`data`),
+/// which is bound beforehand for computed filters, see [`computed_binding`].
+fn filter_value(name: &Ident, computed: Option<&syn::Path>) -> TokenStream {
+    match computed {
+        Some(_) => {
+            let binding = format_ident!("computed_{}", name);
+            quote! { #binding }
+        }
+        None => quote! { data.#name },
+    }
+}
+
+fn computed_binding(name: &Ident, computed: Option<&syn::Path>) -> Option<TokenStream> {
+    computed.map(|with| {
+        let binding = format_ident!("computed_{}", name);
+        quote! { let #binding = #with(data); }
+    })
 }
@@ -1,29 +1,69 @@
-use crate::field::{FieldInfo, StructField};
+use crate::{
+    field::{FieldInfo, IndexField, KeyField, SortField, StructField, WeightField},
+    parse_attrs::ComputedFilterAttr,
+};
 use proc_macro2::TokenStream;
-use quote::{ToTokens, format_ident, quote};
-use syn::{Ident, Path};
+use quote::{ToTokens, format_ident, quote, quote_spanned};
+use syn::{Ident, Path, spanned::Spanned};
 
 /// Generate the venndb logic
+#[allow(clippy::too_many_arguments)]
 pub fn generate_db(
     name: &Ident,
     name_db: &Ident,
     validator: Option<&Path>,
+    clone: Option<&Path>,
     vis: &syn::Visibility,
     fields: &[StructField],
+    keys: &[KeyField],
+    filters: &[&ComputedFilterAttr],
 ) -> TokenStream {
-    let fields: Vec<_> = fields.iter().filter_map(StructField::info).collect();
+    let db_row_mut = generate_row_mut_struct(name, name_db, vis, fields, keys, filters);
+
+    let weight = fields.iter().find_map(StructField::weight);
+
+    // composite keys are indexed just like single field keys,
+    // and computed filters just like the filters of struct fields
+    let fields: Vec<_> = fields
+        .iter()
+        .filter_map(StructField::info)
+        .chain(keys.iter().cloned().map(FieldInfo::Key))
+        .chain(filters.iter().map(|filter| FieldInfo::computed(filter)))
+        .collect();
 
     let db_error = DbError::new(validator, &fields[..]);
 
-    let db_struct = generate_db_struct(name, name_db, vis, &fields[..]);
-    let db_struct_methods =
-        generate_db_struct_methods(name, name_db, validator, vis, &db_error, &fields[..]);
+    let db_struct = generate_db_struct(name, name_db, vis, &fields[..], weight.as_ref());
+    let db_struct_methods = generate_db_struct_methods(
+        name,
+        name_db,
+        validator,
+        clone,
+        vis,
+        &db_error,
+        &fields[..],
+        weight.as_ref(),
+    );
 
-    let db_query = generate_query_struct(name, name_db, vis, &fields[..]);
+    let db_query = generate_query_struct(name, name_db, vis, &fields[..], weight.as_ref());
 
     let db_error_definitions = db_error.generate_definitions(name_db, vis);
 
+    let db_compact_stats = generate_compact_stats_struct(name_db, vis);
+
+    // structs marked with `#[venndb(clone)]` have to implement `Clone`, reported at the attribute
+    let clone_check = clone.map(|clone| {
+        quote_spanned! { clone.span() =>
+            const _: () = {
+                fn assert_clone<T: ::std::clone::Clone>() {}
+                let _ = assert_clone::<#name>;
+            };
+        }
+    });
+
     quote! {
+        #clone_check
+
         #db_struct
 
         #db_struct_methods
@@ -31,6 +71,104 @@ pub fn generate_db(
         #db_query
 
         #db_error_definitions
+
+        #db_compact_stats
+
+        #db_row_mut
+    }
+}
+
+fn generate_row_mut_struct(
+    name: &Ident,
+    name_db: &Ident,
+    vis: &syn::Visibility,
+    fields: &[StructField],
+    keys: &[KeyField],
+    filters: &[&ComputedFilterAttr],
+) -> TokenStream {
+    let name_row_mut = format_ident!("{}RowMut", name_db);
+
+    // fields which are part of a composite key are indexed as well,
+    // while computed filters can be derived from any of the fields
+    let is_indexed = |field: &StructField| {
+        !filters.is_empty()
+            || field.info().is_some()
+            || field.weight().is_some()
+            || keys
+                .iter()
+                .any(|key| key.parts().iter().any(|part| part.name == field.name()))
+    };
+
+    let view_fields: Vec<_> = fields
+        .iter()
+        .map(|field| {
+            let field_name = field.name();
+            let field_vis = field.vis();
+            let ty = field.ty();
+            if is_indexed(field) {
+                quote! {
+                    #field_vis #field_name: &'a #ty,
+                }
+            } else {
+                quote! {
+                    #field_vis #field_name: &'a mut #ty,
+                }
+            }
+        })
+        .collect();
+
+    let field_names: Vec<_> = fields.iter().map(StructField::name).collect();
+
+    // an empty struct still needs to make use of the lifetime
+    let (marker_field, marker_init) = if fields.is_empty() {
+        (
+            quote! { _marker: ::std::marker::PhantomData<&'a mut #name>, },
+            quote! { _marker: ::std::marker::PhantomData, },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    let unindexed_fields = fields
+        .iter()
+        .filter(|field| !is_indexed(field))
+        .map(|field| format!("`{}`", field.name()))
+        .collect::<Vec<_>>();
+    let unindexed_fields = if unindexed_fields.is_empty() {
+        "none".to_owned()
+    } else {
+        unindexed_fields.join(", ")
+    };
+
+    let mut doc = format!(
+        "A mutable view of an instance of [`{}`] stored within [`{}`], generated by `#[derive(VennDB)]`.\n\n\
+         Only the fields which are not indexed by the database (neither key, filter nor weight) can be mutated, \
+         all other fields are read-only. Mutable fields: {}.",
+        name, name_db, unindexed_fields,
+    );
+    if !filters.is_empty() {
+        doc.push_str(
+            "\n\nAs the computed filters can be derived from any field, none of the fields can be mutated.",
+        );
+    }
+
+    quote! {
+        #[doc=#doc]
+        #[allow(dead_code)]
+        #vis struct #name_row_mut<'a> {
+            #(#view_fields)*
+            #marker_field
+        }
+
+        impl<'a> #name_row_mut<'a> {
+            fn new(row: &'a mut #name) -> Self {
+                let #name { #(#field_names,)* } = row;
+                Self {
+                    #(#field_names,)*
+                    #marker_init
+                }
+            }
+        }
     }
 }
 
@@ -39,13 +177,14 @@ fn generate_db_struct(
     name_db: &Ident,
     vis: &syn::Visibility,
     fields: &[FieldInfo],
+    weight: Option<&WeightField>,
 ) -> TokenStream {
     let db_fields: Vec<_> = fields
         .iter()
         .map(|info| match info {
             FieldInfo::Key(field) => {
                 let field_name = field.map_name();
-                let ty: &syn::Type = field.ty();
+                let ty = key_ty(field);
                 quote! {
                     #field_name: ::venndb::__internal::HashMap<#ty, usize>,
                 }
@@ -53,9 +192,16 @@ fn generate_db_struct(
             FieldInfo::Filter(field) => {
                 let field_name = field.filter_name();
                 let field_name_not = field.filter_not_name();
+                let filter_some = match field.filter_some_name() {
+                    Some(name) => quote! {
+                        #name: ::venndb::__internal::BitVec,
+                    },
+                    None => quote! {},
+                };
                 quote! {
                     #field_name: ::venndb::__internal::BitVec,
                     #field_name_not: ::venndb::__internal::BitVec,
+                    #filter_some
                 }
             }
             FieldInfo::FilterMap(field) => {
@@ -67,16 +213,50 @@ fn generate_db_struct(
                     },
                     None => quote! {},
                 };
+                let filter_some = match field.filter_some_name() {
+                    Some(name) => quote! {
+                        #name: ::venndb::__internal::BitVec,
+                    },
+                    None => quote! {},
+                };
                 let ty: &syn::Type = field.ty();
                 quote! {
                     #filter_map_name: ::venndb::__internal::HashMap<#ty, usize>,
                     #filter_vec_name: ::std::vec::Vec<::venndb::__internal::BitVec>,
                     #filter_any
+                    #filter_some
+                }
+            }
+            FieldInfo::Sort(field) => {
+                let sort_name = field.sort_name();
+                let range_index = field.range_index_name().map(|range_index_name| {
+                    let ty: &syn::Type = field.ty();
+                    quote! {
+                        #range_index_name: ::venndb::__internal::RangeIndex<#ty>,
+                    }
+                });
+                quote! {
+                    #sort_name: ::std::vec::Vec<usize>,
+                    #range_index
+                }
+            }
+            FieldInfo::Index(field) => {
+                let index_name = field.index_name();
+                let ty: &syn::Type = field.ty();
+                quote! {
+                    #index_name: ::venndb::__internal::HashMap<#ty, ::std::vec::Vec<usize>>,
                 }
             }
         })
         .collect();
 
+    // the weights of the rows, used to select rows proportionally to their weight
+    let weights = weight.map(|_| {
+        quote! {
+            weights: ::venndb::__internal::WeightIndex,
+        }
+    });
+
     let db_doc = format!(
         "An in-memory database for storing instances of [`{}`], generated by `#[derive(VennDB)]`.",
         name
@@ -86,26 +266,43 @@ fn generate_db_struct(
         #[derive(Debug, Default)]
         #vis struct #name_db {
             rows: Vec<#name>,
+            tombstones: ::venndb::__internal::BitVec,
+            /// The amount of rows marked in `tombstones`.
+            removed: usize,
             #(#db_fields)*
+            #weights
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn generate_db_struct_methods(
     name: &Ident,
     name_db: &Ident,
     validator: Option<&Path>,
+    clone: Option<&Path>,
     vis: &syn::Visibility,
     db_error: &DbError,
     fields: &[FieldInfo],
+    weight: Option<&WeightField>,
 ) -> TokenStream {
-    let method_new = generate_db_struct_method_new(name, name_db, vis, fields);
-    let method_with_capacity = generate_db_struct_method_with_capacity(name, name_db, vis, fields);
+    let name_row_mut = format_ident!("{}RowMut", name_db);
+    let method_new = generate_db_struct_method_new(name, name_db, vis, fields, weight);
+    let method_with_capacity =
+        generate_db_struct_method_with_capacity(name, name_db, vis, fields, weight);
     let method_from_rows =
-        generate_db_struct_method_from_rows(name, name_db, vis, db_error, fields);
+        generate_db_struct_method_from_rows(name, name_db, vis, db_error, fields, weight);
     let field_methods = generate_db_struct_field_methods(name, name_db, vis, fields);
     let method_append =
-        generate_db_struct_method_append(name, name_db, validator, vis, db_error, fields);
+        generate_db_struct_method_append(name, name_db, validator, vis, db_error, fields, weight);
+    let method_remove = generate_db_struct_method_remove(name, name_db, vis, fields, weight);
+    let method_compact = generate_db_struct_method_compact(name, name_db, vis, fields, weight);
+    let method_update = generate_db_struct_method_update(
+        name, name_db, validator, clone, vis, db_error, fields, weight,
+    );
+    let method_upsert =
+        generate_db_struct_method_upsert(name, name_db, vis, db_error, fields, weight);
+    let method_row_weight = generate_db_struct_method_row_weight(name, weight);
 
     quote! {
         #[allow(clippy::unused_unit)]
@@ -118,7 +315,7 @@ fn generate_db_struct_methods(
 
             /// Return the number of rows in the database.
             #vis fn len(&self) -> usize {
-                self.rows.len()
+                self.rows.len() - self.removed
             }
 
             /// Return the capacity of the database,
@@ -129,21 +326,55 @@ fn generate_db_struct_methods(
 
             /// Return `true` if the database is empty.
             #vis fn is_empty(&self) -> bool {
-                self.rows.is_empty()
+                self.len() == 0
             }
 
             /// Return an iterator over the rows in the database.
             #vis fn iter(&self) -> impl ::std::iter::Iterator<Item = &#name> {
-                self.rows.iter()
+                self.rows
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| !self.tombstones.get(*index))
+                    .map(|(_, row)| row)
+            }
+
+            /// Return an iterator over mutable views of the rows in the database,
+            /// allowing to mutate only the fields which are not indexed.
+            #vis fn iter_mut_unindexed(&mut self) -> impl ::std::iter::Iterator<Item = #name_row_mut<'_>> {
+                let tombstones = &self.tombstones;
+                self.rows
+                    .iter_mut()
+                    .enumerate()
+                    .filter(move |(index, _)| !tombstones.get(*index))
+                    .map(|(_, row)| #name_row_mut::new(row))
             }
 
             #field_methods
 
             #method_append
 
+            #method_update
+
+            #method_upsert
+
+            #method_remove
+
+            #method_compact
+
+            #method_row_weight
+
             /// Consumes the database and returns the rows.
             #vis fn into_rows(self) -> ::std::vec::Vec<#name> {
-                self.rows
+                let mut rows = self.rows;
+                if self.removed > 0 {
+                    let mut index = 0;
+                    rows.retain(|_| {
+                        let removed = self.tombstones.get(index);
+                        index += 1;
+                        !removed
+                    });
+                }
+                rows
             }
         }
     }
@@ -154,12 +385,19 @@ fn generate_db_struct_method_new(
     _name_db: &Ident,
     vis: &syn::Visibility,
     fields: &[FieldInfo],
+    weight: Option<&WeightField>,
 ) -> TokenStream {
     let method_doc = format!(
         "Construct a new empty database for storing instances of [`{}`].",
         name
     );
 
+    let weights = weight.map(|_| {
+        quote! {
+            weights: ::venndb::__internal::WeightIndex::new(),
+        }
+    });
+
     let db_fields_initialisers: Vec<_> = fields
         .iter()
         .map(|info| match info {
@@ -172,9 +410,16 @@ fn generate_db_struct_method_new(
             FieldInfo::Filter(field) => {
                 let name = field.filter_name();
                 let name_not = field.filter_not_name();
+                let filter_some = match field.filter_some_name() {
+                    Some(name) => quote! {
+                        #name: ::venndb::__internal::BitVec::new(),
+                    },
+                    None => quote! {},
+                };
                 quote! {
                     #name: ::venndb::__internal::BitVec::new(),
                     #name_not: ::venndb::__internal::BitVec::new(),
+                    #filter_some
                 }
             }
             FieldInfo::FilterMap(field) => {
@@ -186,10 +431,35 @@ fn generate_db_struct_method_new(
                     },
                     None => quote! {},
                 };
+                let filter_some = match field.filter_some_name() {
+                    Some(name) => quote! {
+                        #name: ::venndb::__internal::BitVec::new(),
+                    },
+                    None => quote! {},
+                };
                 quote! {
                     #filter_map_name: ::venndb::__internal::HashMap::new(),
                     #filter_vec_name: ::std::vec::Vec::new(),
                     #filter_any
+                    #filter_some
+                }
+            }
+            FieldInfo::Sort(field) => {
+                let sort_name = field.sort_name();
+                let range_index = field.range_index_name().map(|range_index_name| {
+                    quote! {
+                        #range_index_name: ::venndb::__internal::RangeIndex::new(),
+                    }
+                });
+                quote! {
+                    #sort_name: ::std::vec::Vec::new(),
+                    #range_index
+                }
+            }
+            FieldInfo::Index(field) => {
+                let index_name = field.index_name();
+                quote! {
+                    #index_name: ::venndb::__internal::HashMap::new(),
                 }
             }
         })
@@ -200,7 +470,10 @@ fn generate_db_struct_method_new(
         #vis fn new() -> Self {
             Self {
                 rows: Vec::new(),
+                tombstones: ::venndb::__internal::BitVec::new(),
+                removed: 0,
                 #(#db_fields_initialisers)*
+                #weights
             }
         }
     }
@@ -211,12 +484,19 @@ fn generate_db_struct_method_with_capacity(
     _name_db: &Ident,
     vis: &syn::Visibility,
     fields: &[FieldInfo],
+    weight: Option<&WeightField>,
 ) -> TokenStream {
     let method_doc = format!(
         "Construct a new empty database for storing instances of [`{}`] with a given capacity.",
         name
     );
 
+    let weights = weight.map(|_| {
+        quote! {
+            weights: ::venndb::__internal::WeightIndex::with_capacity(capacity),
+        }
+    });
+
     let db_fields_initialisers_with_capacity: Vec<_> = fields
         .iter()
         .map(|info| match info {
@@ -229,9 +509,16 @@ fn generate_db_struct_method_with_capacity(
             FieldInfo::Filter(field) => {
                 let name = field.filter_name();
                 let name_not = field.filter_not_name();
+                let filter_some = match field.filter_some_name() {
+                    Some(name) => quote! {
+                        #name: ::venndb::__internal::BitVec::with_capacity(capacity),
+                    },
+                    None => quote! {},
+                };
                 quote! {
                     #name: ::venndb::__internal::BitVec::with_capacity(capacity),
                     #name_not: ::venndb::__internal::BitVec::with_capacity(capacity),
+                    #filter_some
                 }
             }
             FieldInfo::FilterMap(field) => {
@@ -243,10 +530,35 @@ fn generate_db_struct_method_with_capacity(
                     },
                     None => quote! {},
                 };
+                let filter_some = match field.filter_some_name() {
+                    Some(name) => quote! {
+                        #name: ::venndb::__internal::BitVec::with_capacity(capacity),
+                    },
+                    None => quote! {},
+                };
                 quote! {
                     #filter_map_name: ::venndb::__internal::HashMap::with_capacity(capacity),
                     #filter_vec_name: ::std::vec::Vec::with_capacity(capacity),
                     #filter_any
+                    #filter_some
+                }
+            }
+            FieldInfo::Sort(field) => {
+                let sort_name = field.sort_name();
+                let range_index = field.range_index_name().map(|range_index_name| {
+                    quote! {
+                        #range_index_name: ::venndb::__internal::RangeIndex::new(),
+                    }
+                });
+                quote! {
+                    #sort_name: ::std::vec::Vec::with_capacity(capacity),
+                    #range_index
+                }
+            }
+            FieldInfo::Index(field) => {
+                let index_name = field.index_name();
+                quote! {
+                    #index_name: ::venndb::__internal::HashMap::with_capacity(capacity),
                 }
             }
         })
@@ -257,7 +569,10 @@ fn generate_db_struct_method_with_capacity(
         #vis fn with_capacity(capacity: usize) -> Self {
             Self {
                 rows: Vec::new(),
+                tombstones: ::venndb::__internal::BitVec::with_capacity(capacity),
+                removed: 0,
                 #(#db_fields_initialisers_with_capacity)*
+                #weights
             }
         }
     }
@@ -268,7 +583,8 @@ fn generate_db_struct_method_from_rows(
     name_db: &Ident,
     vis: &syn::Visibility,
     db_error: &DbError,
-    _fields: &[FieldInfo],
+    fields: &[FieldInfo],
+    weight: Option<&WeightField>,
 ) -> TokenStream {
     let method_doc = format!(
         "Construct a new database from the given set of [`{}`] rows.",
@@ -293,6 +609,15 @@ fn generate_db_struct_method_from_rows(
     );
     let fn_result = db_error.generate_fn_return_value_ok(quote! { db });
 
+    // index all rows at once, rather than one by one as done when appending
+    let db_field_sorts = if has_appended_indices(fields, weight) {
+        quote! {
+            db.index_appended(0);
+        }
+    } else {
+        quote! {}
+    };
+
     quote! {
         #[doc=#method_doc]
         #vis fn from_rows(rows: ::std::vec::Vec<#name>) -> #return_type {
@@ -301,6 +626,7 @@ fn generate_db_struct_method_from_rows(
                 #append_internal_call
             }
             db.rows = rows;
+            #db_field_sorts
             #fn_result
         }
 
@@ -323,6 +649,7 @@ fn generate_db_struct_method_append(
     vis: &syn::Visibility,
     db_error: &DbError,
     fields: &[FieldInfo],
+    weight: Option<&WeightField>,
 ) -> TokenStream {
     let method_doc = format!("Append a new instance of [`{}`] to the database.", name);
     let method_iter_doc = format!(
@@ -352,19 +679,40 @@ fn generate_db_struct_method_append(
                 let db_duplicate_error_kind_creation = DbError::generate_duplicate_key_error_kind_creation(
                     name_db,
                 );
+                let value = key_value(field, quote! { data });
 
-                Some(quote! {
-                    let #entry_field_name = match self.#map_name.entry(data.#field_name.clone()) {
-                        ::venndb::__internal::hash_map::Entry::Occupied(_) => return Err(#db_duplicate_error_kind_creation),
-                        ::venndb::__internal::hash_map::Entry::Vacant(entry) => entry,
-                    };
-                })
+                if field.optional {
+                    // rows without a key value are not registered for that key
+                    Some(quote! {
+                        let #entry_field_name = match #value {
+                            Some(value) => Some(match self.#map_name.entry(value) {
+                                ::venndb::__internal::hash_map::Entry::Occupied(_) => return Err(#db_duplicate_error_kind_creation),
+                                ::venndb::__internal::hash_map::Entry::Vacant(entry) => entry,
+                            }),
+                            None => None,
+                        };
+                    })
+                } else {
+                    Some(quote! {
+                        let #entry_field_name = match self.#map_name.entry(#value) {
+                            ::venndb::__internal::hash_map::Entry::Occupied(_) => return Err(#db_duplicate_error_kind_creation),
+                            ::venndb::__internal::hash_map::Entry::Vacant(entry) => entry,
+                        };
+                    })
+                }
             }
             FieldInfo::Filter(_) =>  None,
             FieldInfo::FilterMap(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
         })
         .collect();
 
+    let computed_bindings: Vec<_> = fields
+        .iter()
+        .filter_map(FieldInfo::computed_binding)
+        .collect();
+
     let db_field_insert_commits: Vec<_> = fields
         .iter()
         .map(|info| match info {
@@ -372,17 +720,26 @@ fn generate_db_struct_method_append(
                 let field_name = field.name();
                 let entry_field_name = format_ident!("entry_{}", field_name);
 
-                quote! {
-                    #entry_field_name.insert(index);
+                if field.optional {
+                    quote! {
+                        if let Some(entry) = #entry_field_name {
+                            entry.insert(index);
+                        }
+                    }
+                } else {
+                    quote! {
+                        #entry_field_name.insert(index);
+                    }
                 }
             }
             FieldInfo::Filter(field) => {
-                let name = field.name();
+                let value = field.value();
                 let field_name = field.filter_name();
                 let field_name_not = field.filter_not_name();
-                if field.optional {
+                if let Some(filter_some_name) = field.filter_some_name() {
                     quote! {
-                        match data.#name {
+                        self.#filter_some_name.push(#value.is_some());
+                        match #value {
                             Some(value) => {
                                 self.#field_name.push(value);
                                 self.#field_name_not.push(!value);
@@ -395,13 +752,13 @@ fn generate_db_struct_method_append(
                     }
                 } else {
                     quote! {
-                        self.#field_name.push(data.#name);
-                        self.#field_name_not.push(!data.#name);
+                        self.#field_name.push(#value);
+                        self.#field_name_not.push(!#value);
                     }
                 }
             }
             FieldInfo::FilterMap(field) => {
-                let name = field.name();
+                let value = field.value();
                 let filter_map_name = field.filter_map_name();
                 let filter_vec_name = field.filter_vec_name();
                 let filter_index = format_ident!("{}_index", filter_vec_name);
@@ -416,11 +773,11 @@ fn generate_db_struct_method_append(
                 let filter_any_register = match field.filter_any_name() {
                     Some(any_vec) => if field.optional {
                         quote! {
-                            self.#any_vec.push(data.#name.as_ref().map(::venndb::Any::is_any).unwrap_or_default());
+                            self.#any_vec.push(#value.as_ref().map(::venndb::Any::is_any).unwrap_or_default());
                         }
                     } else {
                         quote! {
-                            self.#any_vec.push(::venndb::Any::is_any(&data.#name));
+                            self.#any_vec.push(::venndb::Any::is_any(&#value));
                         }
                     },
                     None => quote! {},
@@ -441,11 +798,11 @@ fn generate_db_struct_method_append(
                 };
                 let is_any_value = if field.optional {
                     quote! {
-                        data.#name.as_ref().map(|v| ::venndb::Any::is_any(v)).unwrap_or_default()
+                        #value.as_ref().map(|v| ::venndb::Any::is_any(v)).unwrap_or_default()
                     }
                 } else {
                     quote! {
-                        ::venndb::Any::is_any(&data.#name)
+                        ::venndb::Any::is_any(&#value)
                     }
                 };
                 let register_rows = if field.any {
@@ -462,10 +819,37 @@ fn generate_db_struct_method_append(
                     register_rows
                 };
 
-                if field.optional {
+                let filter_some_register = match field.filter_some_name() {
+                    Some(some_vec) => quote! {
+                        self.#some_vec.push(#value.is_some());
+                    },
+                    None => quote! {},
+                };
+
+                if field.multi {
+                    // rows of collection fields are registered for each of the contained values
+                    quote! {
+                        for row in self.#filter_vec_name.iter_mut() {
+                            row.push(false);
+                        }
+                        for value in &#value {
+                            let #filter_index = match self.#filter_map_name.entry(value.clone()) {
+                                ::venndb::__internal::hash_map::Entry::Occupied(entry) => *entry.get(),
+                                ::venndb::__internal::hash_map::Entry::Vacant(entry) => {
+                                    let vec_index = self.#filter_vec_name.len();
+                                    entry.insert(vec_index);
+                                    self.#filter_vec_name.push(::venndb::__internal::BitVec::repeat(false, index + 1));
+                                    vec_index
+                                }
+                            };
+                            self.#filter_vec_name[#filter_index].set(index, true);
+                        }
+                    }
+                } else if field.optional {
                     quote! {
                         #filter_any_register
-                        let #filter_index = match data.#name.clone() {
+                        #filter_some_register
+                        let #filter_index = match #value.clone() {
                             Some(value) => {
                                 Some(match self.#filter_map_name.entry(value) {
                                     ::venndb::__internal::hash_map::Entry::Occupied(entry) => *entry.get(),
@@ -486,7 +870,7 @@ fn generate_db_struct_method_append(
                 } else {
                     quote! {
                         #filter_any_register
-                        let #filter_index = match self.#filter_map_name.entry(data.#name.clone()) {
+                        let #filter_index = match self.#filter_map_name.entry(#value.clone()) {
                             ::venndb::__internal::hash_map::Entry::Occupied(entry) => *entry.get(),
                             ::venndb::__internal::hash_map::Entry::Vacant(entry) => {
                                 let vec_index = self.#filter_vec_name.len();
@@ -501,9 +885,129 @@ fn generate_db_struct_method_append(
                     }
                 }
             }
+            // sorted once the row is pushed, see `push_internal`
+            FieldInfo::Sort(_) => quote! {},
+            FieldInfo::Index(field) => {
+                let name = field.name();
+                let index_name = field.index_name();
+                // rows are appended last, keeping the indices of each value in ascending order
+                if field.optional {
+                    quote! {
+                        if let Some(value) = data.#name.clone() {
+                            self.#index_name.entry(value).or_default().push(index);
+                        }
+                    }
+                } else {
+                    quote! {
+                        self.#index_name.entry(data.#name.clone()).or_default().push(index);
+                    }
+                }
+            }
+        })
+        .collect();
+
+    let db_field_sort_inserts: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Sort(field) => {
+                let name = field.name();
+                let sort_name = field.sort_name();
+                let range_index_extend = generate_range_index_rows(field, quote! { extend });
+                Some(quote! {
+                    let rows = &self.rows;
+                    let position = self.#sort_name.partition_point(|i| rows[*i].#name <= rows[index].#name);
+                    self.#sort_name.insert(position, index);
+                    #range_index_extend
+                })
+            }
+            FieldInfo::Key(_) => None,
+            FieldInfo::Filter(_) => None,
+            FieldInfo::FilterMap(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    // rows appended in bulk are sorted at once and merged into the existing permutation
+    let db_field_sort_merges: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Sort(field) => {
+                let name = field.name();
+                let sort_name = field.sort_name();
+                let range_index_extend = generate_range_index_rows(field, quote! { extend });
+                Some(quote! {
+                    let rows = &self.rows;
+                    let mut appended: ::std::vec::Vec<usize> = (start..rows.len()).collect();
+                    appended.sort_by(|a, b| rows[*a].#name.cmp(&rows[*b].#name));
+                    if self.#sort_name.is_empty() {
+                        self.#sort_name = appended;
+                    } else {
+                        let mut existing = ::std::mem::take(&mut self.#sort_name).into_iter().peekable();
+                        let mut appended = appended.into_iter().peekable();
+                        let mut merged = ::std::vec::Vec::with_capacity(rows.len());
+                        loop {
+                            // existing rows go first in case of equal values
+                            let next = match (existing.peek(), appended.peek()) {
+                                (Some(a), Some(b)) => if rows[*a].#name <= rows[*b].#name {
+                                    existing.next()
+                                } else {
+                                    appended.next()
+                                },
+                                (Some(_), None) => existing.next(),
+                                (None, Some(_)) => appended.next(),
+                                (None, None) => break,
+                            };
+                            merged.extend(next);
+                        }
+                        self.#sort_name = merged;
+                    }
+                    #range_index_extend
+                })
+            }
+            FieldInfo::Key(_) => None,
+            FieldInfo::Filter(_) => None,
+            FieldInfo::FilterMap(_) => None,
+            FieldInfo::Index(_) => None,
         })
         .collect();
 
+    let weights_push_appended = weight.map(|_| {
+        quote! {
+            for index in start..self.rows.len() {
+                let weight = self.row_weight(index);
+                self.weights.push(weight);
+            }
+        }
+    });
+
+    let (extend_call, index_appended) = if has_appended_indices(fields, weight) {
+        (
+            quote! {
+                let start = self.rows.len();
+                let result = self.extend_unindexed(iter);
+                self.index_appended(start);
+                result
+            },
+            quote! {
+                /// Index the rows appended starting from the given index, for all sorted columns
+                /// and weights, in `O(n + k log k)` rather than indexing them one by one.
+                fn index_appended(&mut self, start: usize) {
+                    #({ #db_field_sort_merges })*
+                    #weights_push_appended
+                }
+            },
+        )
+    } else {
+        (quote! { self.extend_unindexed(iter) }, quote! {})
+    };
+
+    let weights_push = weight.map(|_| {
+        quote! {
+            let weight = self.row_weight(index);
+            self.weights.push(weight);
+        }
+    });
+
     let append_return_type = db_error.generate_fn_output(name_db, quote! { #name }, quote! { () });
     let extend_return_type =
         db_error.generate_fn_output(name_db, quote! { (#name, I::IntoIter) }, quote! { () });
@@ -533,7 +1037,7 @@ fn generate_db_struct_method_append(
             let index = self.rows.len();
             let data = data.into();
             #append_internal_call
-            self.rows.push(data);
+            self.push_internal(data);
             #append_return_output
         }
 
@@ -542,6 +1046,15 @@ fn generate_db_struct_method_append(
             where
                 I: ::std::iter::IntoIterator<Item = Item>,
                 Item: ::std::convert::Into<#name>,
+        {
+            #extend_call
+        }
+
+        /// Append all rows without maintaining the sorted columns and weights, see `index_appended`.
+        fn extend_unindexed<I, Item>(&mut self, iter: I) -> #extend_return_type
+            where
+                I: ::std::iter::IntoIterator<Item = Item>,
+                Item: ::std::convert::Into<#name>,
         {
             let mut index = self.rows.len();
             let mut iter = iter.into_iter();
@@ -554,51 +1067,1183 @@ fn generate_db_struct_method_append(
             #append_return_output
         }
 
+        #index_appended
+
+        /// Push a row which was already appended using `append_internal`.
+        fn push_internal(&mut self, data: #name) {
+            let index = self.rows.len();
+            self.rows.push(data);
+            #(#db_field_sort_inserts)*
+            #weights_push
+        }
+
         fn append_internal(&mut self, data: &#name, index: usize) -> #append_kind_return_type {
             #validator_check
             #(#db_field_insert_checks)*
+            #(#computed_bindings)*
             #(#db_field_insert_commits)*
+            self.tombstones.push(false);
             #append_return_output
         }
     }
 }
 
-fn generate_db_struct_field_methods(
+#[allow(clippy::too_many_arguments)]
+fn generate_db_struct_method_update(
     name: &Ident,
-    _name_db: &Ident,
+    name_db: &Ident,
+    validator: Option<&Path>,
+    clone: Option<&Path>,
     vis: &syn::Visibility,
+    db_error: &DbError,
     fields: &[FieldInfo],
+    weight: Option<&WeightField>,
 ) -> TokenStream {
-    let db_key_methods: Vec<_> = fields
+    if !fields.iter().any(|info| matches!(info, FieldInfo::Key(_))) {
+        // rows can only be updated when they can be looked up by key
+        return TokenStream::new();
+    }
+
+    let validator_check = match validator {
+        Some(validator) => {
+            let err = DbError::generate_invalid_row_error_kind_creation(name_db);
+            quote! {
+                if !#validator(data) {
+                    return Err(#err);
+                }
+            }
+        }
+        None => quote! {},
+    };
+
+    let db_field_replace_checks: Vec<_> = fields
         .iter()
         .filter_map(|info| match info {
             FieldInfo::Key(field) => {
                 let map_name = field.map_name();
-                let ty = field.ty();
-                let method_name = field.method_name();
-                let doc = format!(
-                    "Get an instance of [`{}`] by its key `{}`, if it exists in the database.",
-                    name,
-                    field.name()
+                let db_duplicate_error_kind_creation =
+                    DbError::generate_duplicate_key_error_kind_creation(name_db);
+                let changed = key_changed(field, quote! { old }, quote! { data });
+                let exists = if field.optional {
+                    let lookup = key_lookup(field, quote! { data });
+                    quote! { #lookup.is_some_and(|key| self.#map_name.contains_key(key)) }
+                } else {
+                    let lookup = key_lookup(field, quote! { data });
+                    quote! { self.#map_name.contains_key(#lookup) }
+                };
+                Some(quote! {
+                    if #changed && #exists {
+                        return Err(#db_duplicate_error_kind_creation);
+                    }
+                })
+            }
+            FieldInfo::Filter(_) => None,
+            FieldInfo::FilterMap(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let computed_bindings: Vec<_> = fields
+        .iter()
+        .filter_map(FieldInfo::computed_binding)
+        .collect();
+
+    let db_field_replace_commits: Vec<_> = fields
+        .iter()
+        .map(|info| match info {
+            FieldInfo::Key(field) => {
+                let map_name = field.map_name();
+                let changed = key_changed(field, quote! { old }, quote! { data });
+                let old_lookup = key_lookup(field, quote! { old });
+                let value = key_value(field, quote! { data });
+                if field.optional {
+                    quote! {
+                        if #changed {
+                            if let Some(key) = #old_lookup {
+                                self.#map_name.remove(key);
+                            }
+                            if let Some(key) = #value {
+                                self.#map_name.insert(key, index);
+                            }
+                        }
+                    }
+                } else {
+                    quote! {
+                        if #changed {
+                            self.#map_name.remove(#old_lookup);
+                            self.#map_name.insert(#value, index);
+                        }
+                    }
+                }
+            }
+            FieldInfo::Filter(field) => {
+                let value = field.value();
+                let field_name = field.filter_name();
+                let field_name_not = field.filter_not_name();
+                if let Some(filter_some_name) = field.filter_some_name() {
+                    quote! {
+                        self.#filter_some_name.set(index, #value.is_some());
+                        match #value {
+                            Some(value) => {
+                                self.#field_name.set(index, value);
+                                self.#field_name_not.set(index, !value);
+                            }
+                            None => {
+                                self.#field_name.set(index, false);
+                                self.#field_name_not.set(index, false);
+                            }
+                        }
+                    }
+                } else {
+                    quote! {
+                        self.#field_name.set(index, #value);
+                        self.#field_name_not.set(index, !#value);
+                    }
+                }
+            }
+            FieldInfo::FilterMap(field) => {
+                let value = field.value();
+                let filter_map_name = field.filter_map_name();
+                let filter_vec_name = field.filter_vec_name();
+                let filter_index = format_ident!("{}_index", filter_vec_name);
+
+                let is_any_value = if field.optional {
+                    quote! {
+                        #value.as_ref().map(|v| ::venndb::Any::is_any(v)).unwrap_or_default()
+                    }
+                } else {
+                    quote! {
+                        ::venndb::Any::is_any(&#value)
+                    }
+                };
+
+                let (filter_any_register, filter_any_backfill) = match field.filter_any_name() {
+                    Some(any_vec) => (
+                        quote! {
+                            self.#any_vec.set(index, #is_any_value);
+                        },
+                        quote! {
+                            let bv = &bv | &self.#any_vec;
+                        },
+                    ),
+                    None => (quote! {}, quote! {}),
+                };
+
+                let lookup_filter_index = |value: TokenStream| {
+                    quote! {
+                        match self.#filter_map_name.entry(#value) {
+                            ::venndb::__internal::hash_map::Entry::Occupied(entry) => *entry.get(),
+                            ::venndb::__internal::hash_map::Entry::Vacant(entry) => {
+                                let vec_index = self.#filter_vec_name.len();
+                                entry.insert(vec_index);
+                                let bv = ::venndb::__internal::BitVec::repeat(false, self.rows.len());
+                                #filter_any_backfill
+                                self.#filter_vec_name.push(bv);
+                                vec_index
+                            }
+                        }
+                    }
+                };
+
+                let register_row = if field.multi {
+                    // rows of collection fields are registered for each of the contained values
+                    let lookup = lookup_filter_index(quote! { value.clone() });
+                    quote! {
+                        for value in &#value {
+                            let #filter_index = #lookup;
+                            self.#filter_vec_name[#filter_index].set(index, true);
+                        }
+                    }
+                } else if field.optional {
+                    let lookup = lookup_filter_index(quote! { value });
+                    quote! {
+                        if let Some(value) = #value.clone() {
+                            let #filter_index = #lookup;
+                            self.#filter_vec_name[#filter_index].set(index, true);
+                        }
+                    }
+                } else {
+                    let lookup = lookup_filter_index(quote! { #value.clone() });
+                    quote! {
+                        let #filter_index = #lookup;
+                        self.#filter_vec_name[#filter_index].set(index, true);
+                    }
+                };
+                let register_row = if field.any {
+                    quote! {
+                        if #is_any_value {
+                            for bv in self.#filter_vec_name.iter_mut() {
+                                bv.set(index, true);
+                            }
+                        } else {
+                            #register_row
+                        }
+                    }
+                } else {
+                    register_row
+                };
+
+                let filter_some_register = match field.filter_some_name() {
+                    Some(some_vec) => quote! {
+                        self.#some_vec.set(index, #value.is_some());
+                    },
+                    None => quote! {},
+                };
+
+                quote! {
+                    #filter_any_register
+                    #filter_some_register
+                    for bv in self.#filter_vec_name.iter_mut() {
+                        bv.set(index, false);
+                    }
+                    #register_row
+                }
+            }
+            FieldInfo::Sort(field) => {
+                let name = field.name();
+                let sort_name = field.sort_name();
+                let range_index_update = field.range_index_name().map(|range_index_name| {
+                    let old_value = field.range_value(quote! { old });
+                    let new_value = field.range_value(quote! { data });
+                    quote! {
+                        self.#range_index_name.update(index, #old_value, #new_value);
+                    }
+                });
+                // rows are sorted by value and index, as such
+                // the position of the row can be found using its (old) value
+                quote! {
+                    if old.#name != data.#name {
+                        let rows = &self.rows;
+                        let position = self.#sort_name.partition_point(|i| {
+                            (&rows[*i].#name, *i) < (&old.#name, index)
+                        });
+                        self.#sort_name.remove(position);
+                        let position = self.#sort_name.partition_point(|i| {
+                            (&rows[*i].#name, *i) < (&data.#name, index)
+                        });
+                        self.#sort_name.insert(position, index);
+                        #range_index_update
+                    }
+                }
+            }
+            FieldInfo::Index(field) => {
+                let name = field.name();
+                let index_name = field.index_name();
+                let index_removal = generate_index_removal(field, quote! { old });
+                let new_value = if field.optional {
+                    quote! { data.#name.clone() }
+                } else {
+                    quote! { Some(data.#name.clone()) }
+                };
+                quote! {
+                    if old.#name != data.#name {
+                        #index_removal
+                        if let Some(value) = #new_value {
+                            let indices = self.#index_name.entry(value).or_default();
+                            let position = indices.partition_point(|i| *i < index);
+                            indices.insert(position, index);
+                        }
+                    }
+                }
+            }
+        })
+        .collect();
+
+    let replace_kind_return_type = db_error.generate_fn_kind_output(name_db, quote! { () });
+    let replace_return_output = db_error.generate_fn_return_value_ok(quote! { () });
+
+    let update_return_type = db_error.generate_fn_output(
+        name_db,
+        quote! { #name },
+        quote! { ::std::option::Option<&#name> },
+    );
+    let replace_internal_call = db_error.generate_fn_error_kind_usage(
+        name_db,
+        quote! {
+            self.replace_internal(index, &data)
+        },
+        quote! { data },
+    );
+
+    let weights_update = weight.map(|_| {
+        quote! {
+            let weight = self.row_weight(index);
+            self.weights.set(index, weight);
+        }
+    });
+
+    // the row is cloned to be able to roll back the mutation
+    let db_key_update_methods: Vec<_> = fields
+        .iter()
+        .filter(|_| clone.is_some())
+        .filter_map(|info| match info {
+            FieldInfo::Key(field) => {
+                let map_name = field.map_name();
+                let KeyParams { generics, args, bounds, lookup } = key_params(field);
+                let method_name = field.update_method_name();
+                let doc = format!(
+                    "Update an instance of [`{}`] by its key {}, using the given function to mutate the row.\n\n\
+                     All keys and filters of the row are re-indexed once mutated. In case the mutated row is no longer valid \
+                     (e.g. a key is duplicate or the validator rejects it) the mutation is rolled back and the rejected row is returned as part of the error.\n\n\
+                     Returns a reference to the updated row, or `None` if no row exists for the given key.\n\n\
+                     This method is only generated for structs marked with `#[venndb(clone)]`, as the row is cloned to roll back the mutation.",
+                    name,
+                    key_doc_name(field),
+                );
+                Some(quote! {
+                    #[doc=#doc]
+                    #vis fn #method_name<#generics F>(&mut self, #args f: F) -> #update_return_type
+                        where
+                            #bounds
+                            F: ::std::ops::FnOnce(&mut #name),
+                    {
+                        let index = match self.#map_name.get(#lookup) {
+                            Some(index) => *index,
+                            None => return Ok(None),
+                        };
+                        let mut data = self.rows[index].clone();
+                        f(&mut data);
+                        #replace_internal_call
+                        self.rows[index] = data;
+                        #weights_update
+                        Ok(Some(&self.rows[index]))
+                    }
+                })
+            }
+            FieldInfo::Filter(_) => None,
+            FieldInfo::FilterMap(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    quote! {
+        #(#db_key_update_methods)*
+
+        fn replace_internal(&mut self, index: usize, data: &#name) -> #replace_kind_return_type {
+            #validator_check
+            let old = &self.rows[index];
+            #(#db_field_replace_checks)*
+            #(#computed_bindings)*
+            #(#db_field_replace_commits)*
+            #replace_return_output
+        }
+    }
+}
+
+/// The generic parameters, their bounds and the method arguments
+/// for the methods looking up a row by the given key,
+/// as well as the lookup of the key in its map using these arguments.
+struct KeyParams {
+    generics: TokenStream,
+    args: TokenStream,
+    bounds: TokenStream,
+    lookup: TokenStream,
+}
+
+fn key_params(field: &KeyField) -> KeyParams {
+    if !field.is_composite() {
+        let ty = key_ty(field);
+        return KeyParams {
+            generics: quote! { Q, },
+            args: quote! { key: &Q, },
+            bounds: quote! {
+                #ty: ::std::borrow::Borrow<Q>,
+                Q: ::std::hash::Hash + ::std::cmp::Eq + ?::std::marker::Sized,
+            },
+            lookup: quote! { key },
+        };
+    }
+    let generics: Vec<_> = (0..field.parts().len())
+        .map(|index| format_ident!("Q{}", index))
+        .collect();
+    let names: Vec<_> = field.parts().iter().map(|part| part.name).collect();
+    let tys = field.parts().iter().map(|part| part.ty);
+    KeyParams {
+        generics: quote! { #(#generics,)* },
+        args: quote! { #(#names: &#generics,)* },
+        bounds: quote! {
+            #(
+                #tys: ::std::borrow::Borrow<#generics>,
+                #generics: ::std::hash::Hash + ::std::cmp::Eq + ?::std::marker::Sized,
+            )*
+        },
+        lookup: quote! { &::venndb::__internal::KeyRef((#(#names,)*)) },
+    }
+}
+
+/// The name of the given key as used in documentation,
+/// e.g. `` `id` `` or `` (`host`, `port`) ``.
+fn key_doc_name(field: &KeyField) -> String {
+    let names: Vec<_> = field
+        .parts()
+        .iter()
+        .map(|part| format!("`{}`", part.name))
+        .collect();
+    if field.is_composite() {
+        format!("({})", names.join(", "))
+    } else {
+        names.concat()
+    }
+}
+
+/// The (owned) type of the given key, a tuple in case of a composite key.
+fn key_ty(field: &KeyField) -> TokenStream {
+    let tys = field.parts().iter().map(|part| part.ty);
+    if field.is_composite() {
+        quote! { (#(#tys,)*) }
+    } else {
+        quote! { #(#tys)* }
+    }
+}
+
+/// The (owned) key of the given row, cloned from its field(s).
+///
+/// For optional keys this is an `Option` of that key instead.
+fn key_value(field: &KeyField, row: TokenStream) -> TokenStream {
+    let names = field.parts().iter().map(|part| part.name);
+    if field.is_composite() {
+        quote! { (#(#row.#names.clone(),)*) }
+    } else {
+        quote! { #(#row.#names.clone())* }
+    }
+}
+
+/// A reference to the key of the given row, used to look up the key in its map.
+///
+/// For optional keys this is an `Option` of that reference instead.
+fn key_lookup(field: &KeyField, row: TokenStream) -> TokenStream {
+    let names = field.parts().iter().map(|part| part.name);
+    if field.optional {
+        quote! { #(#row.#names.as_ref())* }
+    } else if field.is_composite() {
+        quote! { &::venndb::__internal::KeyRef((#(&#row.#names,)*)) }
+    } else {
+        quote! { #(&#row.#names)* }
+    }
+}
+
+/// Whether the key of the `old` row differs from the one of the `new` row.
+fn key_changed(field: &KeyField, old: TokenStream, new: TokenStream) -> TokenStream {
+    let names: Vec<_> = field.parts().iter().map(|part| part.name).collect();
+    if field.is_composite() {
+        quote! { (#(&#old.#names,)*) != (#(&#new.#names,)*) }
+    } else {
+        quote! { #(#old.#names)* != #(#new.#names)* }
+    }
+}
+
+/// Remove the given row (at `index`) from the indices of its value for the given index field,
+/// dropping the value from the index if no other rows have it.
+fn generate_index_removal(field: &IndexField, row: TokenStream) -> TokenStream {
+    let name = field.name();
+    let index_name = field.index_name();
+    let value = if field.optional {
+        quote! { &#row.#name }
+    } else {
+        quote! { Some(&#row.#name) }
+    };
+    quote! {
+        if let Some(value) = #value {
+            if let Some(indices) = self.#index_name.get_mut(value) {
+                indices.retain(|i| *i != index);
+                if indices.is_empty() {
+                    self.#index_name.remove(value);
+                }
+            }
+        }
+    }
+}
+
+/// Call the given method (`extend` or `rebuild`) on the range index of the given sort field,
+/// passing it the sorted rows and a way to look up the value of a row.
+fn generate_range_index_rows(field: &SortField, method: TokenStream) -> Option<TokenStream> {
+    let range_index_name = field.range_index_name()?;
+    let sort_name = field.sort_name();
+    let value = field.range_value(quote! { rows[index] });
+    Some(quote! {
+        let rows = &self.rows;
+        self.#range_index_name.#method(&self.#sort_name, |index| #value);
+    })
+}
+
+fn generate_db_struct_method_upsert(
+    name: &Ident,
+    name_db: &Ident,
+    vis: &syn::Visibility,
+    db_error: &DbError,
+    fields: &[FieldInfo],
+    weight: Option<&WeightField>,
+) -> TokenStream {
+    if !fields.iter().any(|info| matches!(info, FieldInfo::Key(_))) {
+        // rows can only be replaced when they can be looked up by key
+        return TokenStream::new();
+    }
+
+    let method_doc = format!(
+        "Insert an instance of [`{}`] into the database, replacing the existing row with the same key(s) if there is one.\n\n\
+         The replaced row is returned, and the new row is re-indexed in its place. In case the keys of the row \
+         match different existing rows the row is rejected as a duplicate.",
+        name
+    );
+
+    let ident_error = format_ident!("{}Error", name_db);
+    let db_duplicate_error_kind_creation =
+        DbError::generate_duplicate_key_error_kind_creation(name_db);
+
+    let db_key_lookups: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Key(field) => {
+                let map_name = field.map_name();
+                let lookup = key_lookup(field, quote! { data });
+                let other_index = if field.optional {
+                    quote! { #lookup.and_then(|key| self.#map_name.get(key)).copied() }
+                } else {
+                    quote! { self.#map_name.get(#lookup).copied() }
+                };
+                Some(quote! {
+                    if let Some(other_index) = #other_index {
+                        match existing_index {
+                            Some(index) if index != other_index => {
+                                return Err(#ident_error::new(#db_duplicate_error_kind_creation, data, other_index));
+                            }
+                            _ => existing_index = Some(other_index),
+                        }
+                    }
+                })
+            }
+            FieldInfo::Filter(_) => None,
+            FieldInfo::FilterMap(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let upsert_return_type = db_error.generate_fn_output(
+        name_db,
+        quote! { #name },
+        quote! { ::std::option::Option<#name> },
+    );
+    let append_internal_call = db_error.generate_fn_error_kind_usage(
+        name_db,
+        quote! {
+            self.append_internal(&data, index)
+        },
+        quote! { data },
+    );
+    let replace_internal_call = db_error.generate_fn_error_kind_usage(
+        name_db,
+        quote! {
+            self.replace_internal(index, &data)
+        },
+        quote! { data },
+    );
+
+    let weights_update = weight.map(|_| {
+        quote! {
+            let weight = self.row_weight(index);
+            self.weights.set(index, weight);
+        }
+    });
+
+    quote! {
+        #[doc=#method_doc]
+        #vis fn upsert(&mut self, data: impl ::std::convert::Into<#name>) -> #upsert_return_type {
+            let data = data.into();
+
+            let mut existing_index: ::std::option::Option<usize> = None;
+            #(#db_key_lookups)*
+
+            match existing_index {
+                Some(index) => {
+                    #replace_internal_call
+                    let old = ::std::mem::replace(&mut self.rows[index], data);
+                    #weights_update
+                    Ok(Some(old))
+                }
+                None => {
+                    let index = self.rows.len();
+                    #append_internal_call
+                    self.push_internal(data);
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+fn generate_db_struct_method_remove(
+    name: &Ident,
+    _name_db: &Ident,
+    vis: &syn::Visibility,
+    fields: &[FieldInfo],
+    weight: Option<&WeightField>,
+) -> TokenStream {
+    let method_doc = format!(
+        "Remove the instance of [`{}`] stored at the given row index from the database.\n\n\
+         The row is marked as removed and will no longer be returned by lookups, queries or iterators.\n\
+         Returns `false` if no (live) row exists at that index.",
+        name
+    );
+
+    let db_key_removals: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Key(field) => {
+                let map_name = field.map_name();
+                let lookup = key_lookup(field, quote! { row });
+                if field.optional {
+                    Some(quote! {
+                        if let Some(key) = #lookup {
+                            self.#map_name.remove(key);
+                        }
+                    })
+                } else {
+                    Some(quote! {
+                        self.#map_name.remove(#lookup);
+                    })
+                }
+            }
+            FieldInfo::Index(field) => Some(generate_index_removal(field, quote! { row })),
+            FieldInfo::Filter(_) => None,
+            FieldInfo::FilterMap(_) => None,
+            FieldInfo::Sort(_) => None,
+        })
+        .collect();
+
+    let weights_removal = weight.map(|_| {
+        quote! {
+            self.weights.set(index, 0.0);
+        }
+    });
+
+    quote! {
+        #[doc=#method_doc]
+        #vis fn remove_at(&mut self, index: usize) -> bool {
+            if index >= self.rows.len() || self.tombstones.get(index) {
+                return false;
+            }
+            let row = &self.rows[index];
+            #(#db_key_removals)*
+            self.tombstones.set(index, true);
+            self.removed += 1;
+            #weights_removal
+            true
+        }
+    }
+}
+
+fn generate_db_struct_method_compact(
+    name: &Ident,
+    name_db: &Ident,
+    vis: &syn::Visibility,
+    fields: &[FieldInfo],
+    weight: Option<&WeightField>,
+) -> TokenStream {
+    let name_compact_stats = format_ident!("{}CompactStats", name_db);
+
+    let db_field_compactions: Vec<_> = fields
+        .iter()
+        .map(|info| match info {
+            FieldInfo::Key(field) => {
+                let map_name = field.map_name();
+                quote! {
+                    for index in self.#map_name.values_mut() {
+                        *index = remap[*index];
+                    }
+                }
+            }
+            FieldInfo::Filter(field) => {
+                let field_name = field.filter_name();
+                let field_name_not = field.filter_not_name();
+                let filter_some_compaction = field.filter_some_name().map(|filter_some_name| {
+                    quote! {
+                        self.#filter_some_name.retain(&alive);
+                    }
+                });
+                quote! {
+                    self.#field_name.retain(&alive);
+                    self.#field_name_not.retain(&alive);
+                    #filter_some_compaction
+                }
+            }
+            FieldInfo::FilterMap(field) => {
+                let filter_vec_name = field.filter_vec_name();
+                let filter_some_compaction = field.filter_some_name().map(|filter_some_name| {
+                    quote! {
+                        self.#filter_some_name.retain(&alive);
+                    }
+                });
+                let filter_any_compaction = field.filter_any_name().map(|filter_any_name| {
+                    quote! {
+                        self.#filter_any_name.retain(&alive);
+                    }
+                });
+                quote! {
+                    for bv in self.#filter_vec_name.iter_mut() {
+                        bv.retain(&alive);
+                    }
+                    #filter_some_compaction
+                    #filter_any_compaction
+                }
+            }
+            FieldInfo::Sort(field) => {
+                let sort_name = field.sort_name();
+                let range_index_rebuild = generate_range_index_rows(field, quote! { rebuild });
+                // the remapping preserves the order of the remaining rows
+                quote! {
+                    self.#sort_name.retain(|index| alive.get(*index));
+                    for index in self.#sort_name.iter_mut() {
+                        *index = remap[*index];
+                    }
+                    #range_index_rebuild
+                }
+            }
+            FieldInfo::Index(field) => {
+                let index_name = field.index_name();
+                // removed rows are already dropped from the index
+                quote! {
+                    for indices in self.#index_name.values_mut() {
+                        for index in indices.iter_mut() {
+                            *index = remap[*index];
+                        }
+                    }
+                }
+            }
+        })
+        .collect();
+
+    // each filter map compaction is an expression
+    // resulting in the amount of distinct values dropped,
+    // swept regardless of rows being removed as updated rows can leave values unused as well
+    let db_filter_map_compactions: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::FilterMap(field) => {
+                let filter_map_name = field.filter_map_name();
+                let filter_vec_name = field.filter_vec_name();
+                let (filter_any_binding, value_occurs) = match field.filter_any_name() {
+                    Some(filter_any_name) => (
+                        quote! {
+                            let filter_any = &self.#filter_any_name;
+                        },
+                        // rows with an any value are registered for all values,
+                        // so these do not count as occurrences of the other values
+                        quote! {
+                            if ::venndb::Any::is_any(value) {
+                                bv.any()
+                            } else {
+                                !bv.is_subset(filter_any)
+                            }
+                        },
+                    ),
+                    None => (quote! {}, quote! { bv.any() }),
+                };
+                Some(quote! {
+                    {
+                        #filter_any_binding
+
+                        let mut used = ::std::vec![false; self.#filter_vec_name.len()];
+                        let values_len = self.#filter_map_name.len();
+                        let filter_vec = &self.#filter_vec_name;
+                        self.#filter_map_name.retain(|value, vec_index| {
+                            let bv = &filter_vec[*vec_index];
+                            let occurs = #value_occurs;
+                            used[*vec_index] = occurs;
+                            occurs
+                        });
+
+                        let mut vec_remap = ::std::vec::Vec::with_capacity(used.len());
+                        let mut vec_index = 0;
+                        for is_used in used.iter() {
+                            vec_remap.push(vec_index);
+                            if *is_used {
+                                vec_index += 1;
+                            }
+                        }
+                        let mut used = used.into_iter();
+                        self.#filter_vec_name.retain(|_| used.next().unwrap_or_default());
+                        for vec_index in self.#filter_map_name.values_mut() {
+                            *vec_index = vec_remap[*vec_index];
+                        }
+
+                        values_len - self.#filter_map_name.len()
+                    }
+                })
+            }
+            FieldInfo::Key(_) => None,
+            FieldInfo::Filter(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let weights_rebuild = weight.map(|weight| {
+        let weight_value = weight.value(quote! { row });
+        quote! {
+            // no rows are removed at this point
+            let rows = &self.rows;
+            #[allow(clippy::unnecessary_cast)]
+            self.weights.rebuild(rows.iter().map(|row| #weight_value));
+        }
+    });
+
+    quote! {
+        /// Retain only the rows for which the given predicate returns `true`,
+        /// removing all other rows and compacting the database afterwards.
+        ///
+        /// See [`Self::compact`] for more information about the compaction,
+        /// of which the statistics are returned.
+        #vis fn retain<F>(&mut self, mut f: F) -> #name_compact_stats
+            where
+                F: ::std::ops::FnMut(&#name) -> bool,
+        {
+            for index in 0..self.rows.len() {
+                if !self.tombstones.get(index) && !f(&self.rows[index]) {
+                    self.remove_at(index);
+                }
+            }
+            self.compact()
+        }
+
+        /// Compact the database, physically dropping all rows which were removed,
+        /// as well as the filter map values which no longer occur in any (remaining) row.
+        ///
+        /// Row indices are renumbered as part of this process,
+        /// but the order of the remaining rows is preserved.
+        #vis fn compact(&mut self) -> #name_compact_stats {
+            let rows_removed = if self.removed > 0 {
+                self.compact_rows()
+            } else {
+                0
+            };
+
+            let filter_values_removed = 0 #(+ #db_filter_map_compactions)*;
+
+            #name_compact_stats {
+                rows_removed,
+                filter_values_removed,
+            }
+        }
+
+        /// Physically drop all removed rows, renumbering the remaining rows in all indices,
+        /// returning the amount of rows dropped.
+        fn compact_rows(&mut self) -> usize {
+            let mut alive = ::venndb::__internal::BitVec::repeat(true, self.rows.len());
+            alive.and_not(&self.tombstones);
+
+            // old row index => new row index (only valid for alive rows)
+            let mut remap = ::std::vec::Vec::with_capacity(self.rows.len());
+            let mut next_index = 0;
+            for index in 0..self.rows.len() {
+                remap.push(next_index);
+                if alive.get(index) {
+                    next_index += 1;
+                }
+            }
+
+            let rows_len = self.rows.len();
+            let mut index = 0;
+            self.rows.retain(|_| {
+                let keep = alive.get(index);
+                index += 1;
+                keep
+            });
+            let rows_removed = rows_len - self.rows.len();
+
+            #(#db_field_compactions)*
+
+            self.tombstones = ::venndb::__internal::BitVec::repeat(false, self.rows.len());
+            self.removed = 0;
+            #weights_rebuild
+
+            rows_removed
+        }
+    }
+}
+
+/// Whether any columns are maintained for rows appended in bulk,
+/// see the `index_appended` method generated with `append`.
+fn has_appended_indices(fields: &[FieldInfo], weight: Option<&WeightField>) -> bool {
+    weight.is_some() || fields.iter().any(|info| matches!(info, FieldInfo::Sort(_)))
+}
+
+fn generate_db_struct_method_row_weight(name: &Ident, weight: Option<&WeightField>) -> TokenStream {
+    let Some(weight) = weight else {
+        return TokenStream::new();
+    };
+    let weight_value = weight.value(quote! { row });
+
+    quote! {
+        /// The weight of the row at the given index, with removed rows counting as a zero weight.
+        #[allow(clippy::unnecessary_cast)]
+        fn row_weight(&self, index: usize) -> f64 {
+            if self.tombstones.get(index) {
+                return 0.0;
+            }
+            let row: &#name = &self.rows[index];
+            #weight_value
+        }
+    }
+}
+
+fn generate_compact_stats_struct(name_db: &Ident, vis: &syn::Visibility) -> TokenStream {
+    let name_compact_stats = format_ident!("{}CompactStats", name_db);
+    let doc = format!(
+        "Statistics about the data reclaimed by compacting the [`{}`], generated by `#[derive(VennDB)]`.",
+        name_db
+    );
+
+    quote! {
+        #[doc=#doc]
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+        #vis struct #name_compact_stats {
+            /// The number of removed rows which were dropped.
+            #vis rows_removed: usize,
+            /// The number of distinct filter map values which were dropped,
+            /// as they no longer occur in any of the remaining rows.
+            #vis filter_values_removed: usize,
+        }
+    }
+}
+
+fn generate_db_struct_field_methods(
+    name: &Ident,
+    name_db: &Ident,
+    vis: &syn::Visibility,
+    fields: &[FieldInfo],
+) -> TokenStream {
+    let db_key_methods: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Key(field) => {
+                let map_name = field.map_name();
+                let KeyParams { generics, args, bounds, lookup } = key_params(field);
+                let method_name = field.method_name();
+                let doc = format!(
+                    "Get an instance of [`{}`] by its key {}, if it exists in the database.",
+                    name,
+                    key_doc_name(field)
+                );
+                let get_mut_method_name = field.get_mut_method_name();
+                let name_row_mut = format_ident!("{}RowMut", name_db);
+                let get_mut_doc = format!(
+                    "Get a mutable view of an instance of [`{}`] by its key {}, if it exists in the database.\n\n\
+                     Only the fields which are not indexed can be mutated using this view.",
+                    name,
+                    key_doc_name(field)
+                );
+                let remove_method_name = field.remove_method_name();
+                let remove_doc = format!(
+                    "Remove an instance of [`{}`] by its key {}, returning whether such a row existed in the database.",
+                    name,
+                    key_doc_name(field)
+                );
+                Some(quote! {
+                    #[doc=#doc]
+                    #vis fn #method_name<#generics>(&self, #args) -> ::std::option::Option<&#name>
+                        where
+                            #bounds
+                    {
+                        self.#map_name.get(#lookup).and_then(|index| self.rows.get(*index))
+                    }
+
+                    #[doc=#get_mut_doc]
+                    #vis fn #get_mut_method_name<#generics>(&mut self, #args) -> ::std::option::Option<#name_row_mut<'_>>
+                        where
+                            #bounds
+                    {
+                        self.#map_name.get(#lookup).and_then(|index| self.rows.get_mut(*index)).map(#name_row_mut::new)
+                    }
+
+                    #[doc=#remove_doc]
+                    #vis fn #remove_method_name<#generics>(&mut self, #args) -> bool
+                        where
+                            #bounds
+                    {
+                        match self.#map_name.get(#lookup) {
+                            Some(index) => self.remove_at(*index),
+                            None => false,
+                        }
+                    }
+                })
+            }
+            FieldInfo::Filter(_) => None,
+            FieldInfo::FilterMap(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let db_filter_map_methods: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::FilterMap(field) => {
+                let ty = field.ty();
+                let filter_map_name = field.filter_map_name();
+                let filter_vec_name = field.filter_vec_name();
+                let facets_name = field.facets_name();
+                let facets_within_name = field.facets_within_name();
+                let facets_doc = format!(
+                    "Return the amount of instances of [`{}`] for each distinct `{}` value in the database.\n\n\
+                     Values are returned in the order they were first seen, omitting values without any rows. \
+                     Rows with an _any_ value are counted for every value.",
+                    name,
+                    field.name()
+                );
+                let distinct_name = field.distinct_name();
+                let distinct_doc = format!(
+                    "Return an iterator over the distinct `{}` values of the instances of [`{}`] in the database.\n\n\
+                     Values are returned in the order they were first seen.",
+                    field.name(),
+                    name,
+                );
+                let (distinct_excluded, distinct_is_value, has_any_method) = match field.filter_any_name() {
+                    Some(filter_any_name) => {
+                        let has_any_name = field.has_any_name();
+                        let has_any_doc = format!(
+                            "Return `true` if the database contains an instance of [`{}`] with an _any_ `{}` value.",
+                            name,
+                            field.name(),
+                        );
+                        (
+                            quote! { &self.tombstones | &self.#filter_any_name },
+                            quote! { !::venndb::Any::is_any(value) && },
+                            quote! {
+                                #[doc=#has_any_doc]
+                                #vis fn #has_any_name(&self) -> bool {
+                                    !self.#filter_any_name.is_subset(&self.tombstones)
+                                }
+                            },
+                        )
+                    }
+                    None => (quote! { self.tombstones.clone() }, quote! {}, quote! {}),
+                };
+                let distinct_doc_any = field.any.then(|| {
+                    quote! {
+                        ///
+                        /// The _any_ value itself is not returned, use the `has_any_` method for that,
+                        /// nor are values which are only matched by rows with an _any_ value.
+                    }
+                });
+                Some(quote! {
+                    #[doc=#facets_doc]
+                    #vis fn #facets_name(&self) -> ::std::vec::Vec<(&#ty, usize)> {
+                        self.#facets_within_name(&!&self.tombstones)
+                    }
+
+                    #[doc=#distinct_doc]
+                    #distinct_doc_any
+                    #vis fn #distinct_name(&self) -> impl ::std::iter::Iterator<Item = &#ty> {
+                        // rows which do not count as an occurrence of a value
+                        let excluded = #distinct_excluded;
+                        let mut values: ::std::vec::Vec<_> = self.#filter_map_name
+                            .iter()
+                            .filter(|(value, vec_index)| {
+                                #distinct_is_value !self.#filter_vec_name[**vec_index].is_subset(&excluded)
+                            })
+                            .map(|(value, vec_index)| (*vec_index, value))
+                            .collect();
+                        values.sort_unstable_by_key(|(vec_index, _)| *vec_index);
+                        values.into_iter().map(|(_, value)| value)
+                    }
+
+                    #has_any_method
+
+                    fn #facets_within_name(&self, within: &::venndb::__internal::BitVec) -> ::std::vec::Vec<(&#ty, usize)> {
+                        let mut facets: ::std::vec::Vec<_> = self.#filter_map_name
+                            .iter()
+                            .filter_map(|(value, vec_index)| {
+                                let count = self.#filter_vec_name[*vec_index].count_ones_and(within);
+                                (count > 0).then_some((*vec_index, value, count))
+                            })
+                            .collect();
+                        facets.sort_unstable_by_key(|(vec_index, _, _)| *vec_index);
+                        facets.into_iter().map(|(_, value, count)| (value, count)).collect()
+                    }
+                })
+            }
+            FieldInfo::Key(_) => None,
+            FieldInfo::Filter(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let db_sort_methods: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Sort(field) => {
+                let sort_name = field.sort_name();
+                let sorted_by_name = field.sorted_by_name();
+                let doc = format!(
+                    "Return an iterator over all instances of [`{}`] in the database, sorted by `{}` (ascending).\n\n\
+                     Instances with equal values are returned in the order they were inserted.",
+                    name,
+                    field.name()
+                );
+                Some(quote! {
+                    #[doc=#doc]
+                    #vis fn #sorted_by_name(&self) -> impl ::std::iter::DoubleEndedIterator<Item = &#name> {
+                        self.#sort_name
+                            .iter()
+                            .filter(|index| !self.tombstones.get(**index))
+                            .map(|index| &self.rows[*index])
+                    }
+                })
+            }
+            FieldInfo::Key(_) => None,
+            FieldInfo::Filter(_) => None,
+            FieldInfo::FilterMap(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let db_index_methods: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Index(field) => {
+                let index_name = field.index_name();
+                let ty = field.ty();
+                let get_all_by_name = field.get_all_by_name();
+                let doc = format!(
+                    "Return an iterator over all instances of [`{}`] with the given value for `{}`, in the order they were inserted.",
+                    name,
+                    field.name()
                 );
                 Some(quote! {
                     #[doc=#doc]
-                    #vis fn #method_name<Q>(&self, key: &Q) -> ::std::option::Option<&#name>
+                    #vis fn #get_all_by_name<Q>(&self, value: &Q) -> impl ::std::iter::DoubleEndedIterator<Item = &#name> + ::std::iter::ExactSizeIterator
                         where
                             #ty: ::std::borrow::Borrow<Q>,
                             Q: ::std::hash::Hash + ::std::cmp::Eq + ?::std::marker::Sized,
                     {
-                        self.#map_name.get(key).and_then(|index| self.rows.get(*index))
+                        self.#index_name
+                            .get(value)
+                            .map(|indices| indices.as_slice())
+                            .unwrap_or_default()
+                            .iter()
+                            .map(|index| &self.rows[*index])
                     }
                 })
             }
+            FieldInfo::Key(_) => None,
             FieldInfo::Filter(_) => None,
             FieldInfo::FilterMap(_) => None,
+            FieldInfo::Sort(_) => None,
         })
         .collect();
 
     quote! {
         #(#db_key_methods)*
+
+        #(#db_filter_map_methods)*
+
+        #(#db_index_methods)*
+
+        #(#db_sort_methods)*
     }
 }
 
@@ -607,6 +2252,7 @@ fn generate_query_struct(
     name_db: &Ident,
     vis: &syn::Visibility,
     fields: &[FieldInfo],
+    weight: Option<&WeightField>,
 ) -> TokenStream {
     let name_query = format_ident!("{}Query", name_db);
 
@@ -615,18 +2261,47 @@ fn generate_query_struct(
         .filter_map(|info| match info {
             FieldInfo::Filter(field) => {
                 let name = field.name();
+                let is_some_field = field.filter_some_name().map(|_| {
+                    let is_some_name = field.is_some_name();
+                    quote! {
+                        #is_some_name: Option<bool>,
+                    }
+                });
                 Some(quote! {
                     #name: Option<bool>,
+                    #is_some_field
                 })
             }
             FieldInfo::FilterMap(field) => {
                 let name = field.name();
+                let not_name = field.not_name();
                 let ty = field.ty();
+                let is_some_field = field.filter_some_name().map(|_| {
+                    let is_some_name = field.is_some_name();
+                    quote! {
+                        #is_some_name: Option<bool>,
+                    }
+                });
+                let all_of_field = field.all_of_name().map(|all_of_name| {
+                    quote! {
+                        #all_of_name: Vec<#ty>,
+                    }
+                });
                 Some(quote! {
                     #name: Vec<#ty>,
+                    #not_name: Vec<#ty>,
+                    #all_of_field
+                    #is_some_field
                 })
             }
             FieldInfo::Key(_) => None,
+            FieldInfo::Sort(field) => field.range_name().map(|range_name| {
+                let ty = field.ty();
+                quote! {
+                    #range_name: (::std::ops::Bound<#ty>, ::std::ops::Bound<#ty>),
+                }
+            }),
+            FieldInfo::Index(_) => None,
         })
         .collect();
 
@@ -639,21 +2314,55 @@ fn generate_query_struct(
         .filter_map(|info| match info {
             FieldInfo::Filter(field) => {
                 let name = field.name();
+                let is_some_initialiser = field.filter_some_name().map(|_| {
+                    let is_some_name = field.is_some_name();
+                    quote! {
+                        #is_some_name: None,
+                    }
+                });
                 Some(quote! {
                     #name: None,
+                    #is_some_initialiser
                 })
             }
             FieldInfo::FilterMap(field) => {
                 let name = field.name();
+                let not_name = field.not_name();
+                let is_some_initialiser = field.filter_some_name().map(|_| {
+                    let is_some_name = field.is_some_name();
+                    quote! {
+                        #is_some_name: None,
+                    }
+                });
+                let all_of_initialiser = field.all_of_name().map(|all_of_name| {
+                    quote! {
+                        #all_of_name: Vec::new(),
+                    }
+                });
                 Some(quote! {
                     #name: Vec::new(),
+                    #not_name: Vec::new(),
+                    #all_of_initialiser
+                    #is_some_initialiser
                 })
             }
             FieldInfo::Key(_) => None,
+            FieldInfo::Sort(field) => field.range_name().map(|range_name| {
+                quote! {
+                    #range_name: (::std::ops::Bound::Unbounded, ::std::ops::Bound::Unbounded),
+                }
+            }),
+            FieldInfo::Index(_) => None,
         })
         .collect();
 
-    let query_impl = generate_query_struct_impl(name, name_db, &name_query, vis, fields);
+    let query_impl = generate_query_struct_impl(name, name_db, &name_query, vis, fields, weight);
+
+    let query_expr = generate_query_expr(name_db, &name_query, vis, fields);
+
+    let query_cursor = generate_query_cursor(name, &name_query, vis, fields);
+
+    let name_expr = format_ident!("{}Expr", name_db);
 
     let query_doc = format!(
         "A query object for filtering instances of [`{}`], within [`{}`], generated by `#[derive(VennDB)]`.",
@@ -671,34 +2380,436 @@ fn generate_query_struct(
         #vis struct #name_query<'a> {
             db: &'a #name_db,
             #(#query_fields)*
+            exprs: Vec<#name_expr>,
+        }
+
+        impl<'a> #name_query<'a> {
+            fn new(db: &'a #name_db) -> Self {
+                Self {
+                    db,
+                    #(#query_field_initialisers)*
+                    exprs: Vec::new(),
+                }
+            }
+        }
+
+        #query_impl
+
+        #query_expr
+
+        #query_cursor
+
+        impl #name_db {
+            #[doc=#query_method_doc]
+            #vis fn query(&self) -> #name_query {
+                #name_query::new(&self)
+            }
+        }
+    }
+}
+
+fn generate_query_cursor(
+    name: &Ident,
+    name_query: &Ident,
+    vis: &syn::Visibility,
+    fields: &[FieldInfo],
+) -> TokenStream {
+    let name_query_cursor = format_ident!("{}Cursor", name_query);
+
+    let query_field_hashes: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Filter(field) => {
+                let name = field.name();
+                let is_some_hash = field.filter_some_name().map(|_| {
+                    let is_some_name = field.is_some_name();
+                    quote! {
+                        ::std::hash::Hash::hash(&self.#is_some_name, &mut hasher);
+                    }
+                });
+                Some(quote! {
+                    ::std::hash::Hash::hash(&self.#name, &mut hasher);
+                    #is_some_hash
+                })
+            }
+            FieldInfo::FilterMap(field) => {
+                let name = field.name();
+                let not_name = field.not_name();
+                let is_some_hash = field.filter_some_name().map(|_| {
+                    let is_some_name = field.is_some_name();
+                    quote! {
+                        ::std::hash::Hash::hash(&self.#is_some_name, &mut hasher);
+                    }
+                });
+                let all_of_hash = field.all_of_name().map(|all_of_name| {
+                    quote! {
+                        ::std::hash::Hash::hash(&self.#all_of_name, &mut hasher);
+                    }
+                });
+                Some(quote! {
+                    ::std::hash::Hash::hash(&self.#name, &mut hasher);
+                    ::std::hash::Hash::hash(&self.#not_name, &mut hasher);
+                    #all_of_hash
+                    #is_some_hash
+                })
+            }
+            FieldInfo::Key(_) => None,
+            // range values are required to be `Hash`, such that the bounds are part of the signature
+            FieldInfo::Sort(field) => field.range_name().map(|range_name| {
+                quote_spanned! { field.ty().span() =>
+                    ::std::hash::Hash::hash(&self.#range_name, &mut hasher);
+                }
+            }),
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let cursor_doc = format!(
+        "A cursor to cycle through the instances of [`{}`] found by a [`{}`] in a round-robin fashion, generated by `#[derive(VennDB)]`.",
+        name, name_query
+    );
+    let cursor_method_doc_next = format!(
+        "Return the next instance of [`{}`] found by the given query, after the one returned last for the same query.",
+        name
+    );
+
+    quote! {
+        #[doc=#cursor_doc]
+        ///
+        /// The cursor remembers the position of the last returned row for each distinct query
+        /// (as defined by the filters and range bounds set on it),
+        /// such that it can be shared between queries.
+        /// Rows inserted after that position are visited in the same cycle,
+        /// and compacting the database shifts the positions of the remaining rows.
+        #[derive(Debug, Clone, Default)]
+        #vis struct #name_query_cursor {
+            positions: ::venndb::__internal::HashMap<u64, usize>,
+        }
+
+        impl #name_query_cursor {
+            /// Create a new cursor, starting at the first found row for every query.
+            #vis fn new() -> Self {
+                Self::default()
+            }
+
+            #[doc=#cursor_method_doc_next]
+            ///
+            /// It wraps around to the first found row after the last one was returned,
+            /// and returns `None` only in case the query does not find any row.
+            #vis fn next_row<'a>(&mut self, query: &#name_query<'a>) -> Option<&'a #name> {
+                let filter = query.execute_filter()?;
+                let position = self.positions.entry(query.signature()).or_insert(0);
+                let index = filter
+                    .iter_ones_from(*position)
+                    .next()
+                    .or_else(|| filter.iter_ones().next())?;
+                *position = index + 1;
+                Some(&query.db.rows[index])
+            }
+
+            /// Forget the positions of all queries,
+            /// such that every query starts again from its first found row.
+            #vis fn reset(&mut self) {
+                self.positions.clear();
+            }
+        }
+
+        impl<'a> #name_query<'a> {
+            /// Compute a signature of the filters set on this query.
+            fn signature(&self) -> u64 {
+                let mut hasher = ::std::hash::DefaultHasher::new();
+                #(#query_field_hashes)*
+                ::std::hash::Hash::hash(&self.exprs, &mut hasher);
+                ::std::hash::Hasher::finish(&hasher)
+            }
+        }
+    }
+}
+
+fn generate_query_expr(
+    name_db: &Ident,
+    name_query: &Ident,
+    vis: &syn::Visibility,
+    fields: &[FieldInfo],
+) -> TokenStream {
+    let name_expr = format_ident!("{}Expr", name_db);
+    let name_expr_kind = format_ident!("{}Kind", name_expr);
+
+    let expr_kind_variants: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Filter(field) => {
+                let name = field.name();
+                Some(quote! {
+                    #name(bool),
+                })
+            }
+            FieldInfo::FilterMap(field) => {
+                let name = field.name();
+                let ty = field.ty();
+                Some(quote! {
+                    #name(#ty),
+                })
+            }
+            FieldInfo::Key(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let expr_constructors: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Filter(field) => {
+                let name = field.name();
+                let method_name = format_ident!("field_{}", name);
+                let doc = format!(
+                    "Create an expression matching the rows for which the `{}` filter equals the given value.",
+                    name
+                );
+                Some(quote! {
+                    #[doc=#doc]
+                    #vis fn #method_name(value: bool) -> Self {
+                        Self {
+                            kind: #name_expr_kind::#name(value),
+                        }
+                    }
+                })
+            }
+            FieldInfo::FilterMap(field) => {
+                let name = field.name();
+                let ty = field.ty();
+                let method_name = format_ident!("field_{}", name);
+                let doc = format!(
+                    "Create an expression matching the rows for which the `{}` filter-map has the given option.",
+                    name
+                );
+                Some(quote! {
+                    #[doc=#doc]
+                    #vis fn #method_name(value: impl ::std::convert::Into<#ty>) -> Self {
+                        Self {
+                            kind: #name_expr_kind::#name(value.into()),
+                        }
+                    }
+                })
+            }
+            FieldInfo::Key(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let expr_evaluations: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Filter(field) => {
+                let name = field.name();
+                let filter_name = field.filter_name();
+                let filter_not_name = field.filter_not_name();
+                Some(quote! {
+                    #name_expr_kind::#name(true) => {
+                        let rows = db.#filter_name.clone();
+                        (rows.clone(), rows)
+                    }
+                    #name_expr_kind::#name(false) => {
+                        let rows = db.#filter_not_name.clone();
+                        (rows.clone(), rows)
+                    }
+                })
+            }
+            FieldInfo::FilterMap(field) => {
+                let name = field.name();
+                let filter_map_name = field.filter_map_name();
+                let filter_vec_name = field.filter_vec_name();
+                // same semantics as the filter-map filters of the query,
+                // except that an unknown value results in an empty set instead of no result:
+                // rows with an any value might match any value, but only match
+                // the value for certain when looking for the any value itself
+                let evaluation = match field.filter_any_name() {
+                    Some(filter_any_vec) => quote! {
+                        if ::venndb::Any::is_any(value) {
+                            let rows = db.#filter_any_vec.clone();
+                            (rows.clone(), rows)
+                        } else {
+                            match db.#filter_map_name.get(value) {
+                                Some(index) => {
+                                    let rows = db.#filter_vec_name[*index].clone();
+                                    let mut certain = rows.clone();
+                                    certain.and_not(&db.#filter_any_vec);
+                                    (rows, certain)
+                                }
+                                None => (
+                                    db.#filter_any_vec.clone(),
+                                    ::venndb::__internal::BitVec::repeat(false, db.rows.len()),
+                                ),
+                            }
+                        }
+                    },
+                    None => quote! {
+                        match db.#filter_map_name.get(value) {
+                            Some(index) => {
+                                let rows = db.#filter_vec_name[*index].clone();
+                                (rows.clone(), rows)
+                            }
+                            None => {
+                                let rows = ::venndb::__internal::BitVec::repeat(false, db.rows.len());
+                                (rows.clone(), rows)
+                            }
+                        }
+                    },
+                };
+                Some(quote! {
+                    #name_expr_kind::#name(value) => #evaluation,
+                })
+            }
+            FieldInfo::Key(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let expr_doc = format!(
+        "A boolean expression over the filters of [`{}`], to be used with [`{}::expr`], generated by `#[derive(VennDB)]`.",
+        name_db, name_query
+    );
+
+    quote! {
+        #[doc=#expr_doc]
+        ///
+        /// Expressions are composed from the field expressions
+        /// (e.g. `field_<name>`) using [`Self::and`], [`Self::or`] and [`Self::not`].
+        #[derive(Debug, Clone, Hash)]
+        #vis struct #name_expr {
+            kind: #name_expr_kind,
+        }
+
+        #[derive(Debug, Clone, Hash)]
+        #[allow(non_camel_case_types)]
+        enum #name_expr_kind {
+            #(#expr_kind_variants)*
+            And(::std::boxed::Box<#name_expr>, ::std::boxed::Box<#name_expr>),
+            Or(::std::boxed::Box<#name_expr>, ::std::boxed::Box<#name_expr>),
+            Not(::std::boxed::Box<#name_expr>),
+        }
+
+        impl #name_expr {
+            #(#expr_constructors)*
+
+            /// Create an expression matching the rows matched by both expressions.
+            #vis fn and(lhs: Self, rhs: Self) -> Self {
+                Self {
+                    kind: #name_expr_kind::And(::std::boxed::Box::new(lhs), ::std::boxed::Box::new(rhs)),
+                }
+            }
+
+            /// Create an expression matching the rows matched by at least one of both expressions.
+            #vis fn or(lhs: Self, rhs: Self) -> Self {
+                Self {
+                    kind: #name_expr_kind::Or(::std::boxed::Box::new(lhs), ::std::boxed::Box::new(rhs)),
+                }
+            }
+
+            /// Create an expression matching the rows not matched by the given expression.
+            ///
+            /// Note that this includes the rows which have no value (`None`)
+            /// for the optional filters used in the given expression.
+            /// Rows with an any value are only excluded when negating the any value itself,
+            /// the same as the `not_<name>` filters of the query.
+            #[allow(clippy::should_implement_trait)]
+            #vis fn not(expr: Self) -> Self {
+                Self {
+                    kind: #name_expr_kind::Not(::std::boxed::Box::new(expr)),
+                }
+            }
+
+            fn evaluate(&self, db: &#name_db) -> ::venndb::__internal::BitVec {
+                self.evaluate_rows(db).0
+            }
+
+            /// Return the rows which might match this expression,
+            /// and the rows which match it for certain. These only differ
+            /// for rows with an any value, which might match any value.
+            fn evaluate_rows(&self, db: &#name_db) -> (::venndb::__internal::BitVec, ::venndb::__internal::BitVec) {
+                match &self.kind {
+                    #(#expr_evaluations)*
+                    #name_expr_kind::And(lhs, rhs) => {
+                        let (lhs, rhs) = (lhs.evaluate_rows(db), rhs.evaluate_rows(db));
+                        (lhs.0 & &rhs.0, lhs.1 & &rhs.1)
+                    }
+                    #name_expr_kind::Or(lhs, rhs) => {
+                        let (lhs, rhs) = (lhs.evaluate_rows(db), rhs.evaluate_rows(db));
+                        (lhs.0 | &rhs.0, lhs.1 | &rhs.1)
+                    }
+                    #name_expr_kind::Not(expr) => {
+                        let (rows, certain) = expr.evaluate_rows(db);
+                        (!certain, !rows)
+                    }
+                }
+            }
         }
 
         impl<'a> #name_query<'a> {
-            fn new(db: &'a #name_db) -> Self {
-                Self {
-                    db,
-                    #(#query_field_initialisers)*
-                }
+            /// Only match the rows matched by the given expression.
+            ///
+            /// Can be called multiple times, in which case all expressions have to match,
+            /// in combination with all other filters set on this query.
+            #vis fn expr(&mut self, expr: #name_expr) -> &mut Self {
+                self.exprs.push(expr);
+                self
             }
         }
+    }
+}
 
-        #query_impl
+fn generate_query_presence_setters(
+    vis: &syn::Visibility,
+    name: &Ident,
+    is_some_name: &Ident,
+    is_none_name: &Ident,
+) -> TokenStream {
+    let is_some_doc = format!(
+        "Only match the rows which have a value (`Some`) for `{}`.",
+        name
+    );
+    let is_none_doc = format!(
+        "Only match the rows which have no value (`None`) for `{}`.",
+        name
+    );
+    quote! {
+        #[doc=#is_none_doc]
+        #vis fn #is_none_name(&mut self) -> &mut Self {
+            self.#is_some_name = Some(false);
+            self
+        }
 
-        impl #name_db {
-            #[doc=#query_method_doc]
-            #vis fn query(&self) -> #name_query {
-                #name_query::new(&self)
-            }
+        #[doc=#is_some_doc]
+        #vis fn #is_some_name(&mut self) -> &mut Self {
+            self.#is_some_name = Some(true);
+            self
         }
     }
 }
 
+fn generate_query_presence_filter(is_some_name: &Ident, filter_some_name: &Ident) -> TokenStream {
+    quote! {
+        // Filter by the presence of a value, only if it is defined as Some(_).
+        match self.#is_some_name {
+            Some(true) => filter &= &self.db.#filter_some_name,
+            Some(false) => {
+                filter.and_not(&self.db.#filter_some_name);
+            }
+            None => (),
+        };
+    }
+}
+
 fn generate_query_struct_impl(
     name: &Ident,
-    _name_db: &Ident,
+    name_db: &Ident,
     name_query: &Ident,
     vis: &syn::Visibility,
     fields: &[FieldInfo],
+    weight: Option<&WeightField>,
 ) -> TokenStream {
     let filter_setters: Vec<_> = fields
         .iter()
@@ -706,12 +2817,22 @@ fn generate_query_struct_impl(
             FieldInfo::Filter(field) => {
                 let name = field.name();
                 let doc = format!("Enable and set the `{}` filter.", name);
+                let presence_setters = field.filter_some_name().map(|_| {
+                    generate_query_presence_setters(
+                        vis,
+                        name,
+                        &field.is_some_name(),
+                        &field.is_none_name(),
+                    )
+                });
                 Some(quote! {
                     #[doc=#doc]
                     #vis fn #name(&mut self, value: bool) -> &mut Self {
                         self.#name = Some(value);
                         self
                     }
+
+                    #presence_setters
                 })
             }
             FieldInfo::FilterMap(field) => {
@@ -721,15 +2842,107 @@ fn generate_query_struct_impl(
                     "Enable and set the `{}` filter-map with the given option.",
                     name
                 );
+                let presence_setters = field.filter_some_name().map(|_| {
+                    generate_query_presence_setters(
+                        vis,
+                        name,
+                        &field.is_some_name(),
+                        &field.is_none_name(),
+                    )
+                });
+                let not_name = field.not_name();
+                let not_doc = format!(
+                    "Exclude the rows for which the `{}` filter-map has the given option.",
+                    name
+                );
+                let all_of_setter = field.all_of_name().map(|all_of_name| {
+                    let all_of_doc = format!(
+                        "Only match the rows for which the `{}` filter-map contains the given option, \
+                         as well as all other options given to this method.",
+                        name
+                    );
+                    quote! {
+                        #[doc=#all_of_doc]
+                        #vis fn #all_of_name(&mut self, value: impl::std::convert::Into<#ty>) -> &mut Self {
+                            self.#all_of_name.push(value.into());
+                            self
+                        }
+                    }
+                });
                 Some(quote! {
                     #[doc=#doc]
                     #vis fn #name(&mut self, value: impl::std::convert::Into<#ty>) -> &mut Self {
                         self.#name.push(value.into());
                         self
                     }
+
+                    #[doc=#not_doc]
+                    #vis fn #not_name(&mut self, value: impl::std::convert::Into<#ty>) -> &mut Self {
+                        self.#not_name.push(value.into());
+                        self
+                    }
+
+                    #all_of_setter
+
+                    #presence_setters
                 })
             }
             FieldInfo::Key(_) => None,
+            FieldInfo::Sort(field) => field.range_name().map(|range_name| {
+                let name = field.name();
+                let ty = field.ty();
+                let between_name = field.between_name();
+                let between_doc = format!(
+                    "Only match the rows for which `{}` is within the given (inclusive) range.",
+                    name
+                );
+                let gt_name = field.gt_name();
+                let gt_doc = format!(
+                    "Only match the rows for which `{}` is greater than the given value.",
+                    name
+                );
+                let lt_name = field.lt_name();
+                let lt_doc = format!(
+                    "Only match the rows for which `{}` is less than the given value.",
+                    name
+                );
+                let none_doc = field.optional.then(|| {
+                    quote! {
+                        ///
+                        /// Rows without a value (`None`) are never matched.
+                    }
+                });
+                quote! {
+                    #[doc=#between_doc]
+                    #none_doc
+                    #vis fn #between_name(
+                        &mut self,
+                        low: impl::std::convert::Into<#ty>,
+                        high: impl::std::convert::Into<#ty>,
+                    ) -> &mut Self {
+                        self.#range_name = (
+                            ::std::ops::Bound::Included(low.into()),
+                            ::std::ops::Bound::Included(high.into()),
+                        );
+                        self
+                    }
+
+                    #[doc=#gt_doc]
+                    #none_doc
+                    #vis fn #gt_name(&mut self, value: impl::std::convert::Into<#ty>) -> &mut Self {
+                        self.#range_name.0 = ::std::ops::Bound::Excluded(value.into());
+                        self
+                    }
+
+                    #[doc=#lt_doc]
+                    #none_doc
+                    #vis fn #lt_name(&mut self, value: impl::std::convert::Into<#ty>) -> &mut Self {
+                        self.#range_name.1 = ::std::ops::Bound::Excluded(value.into());
+                        self
+                    }
+                }
+            }),
+            FieldInfo::Index(_) => None,
         })
         .collect();
 
@@ -738,17 +2951,45 @@ fn generate_query_struct_impl(
         .filter_map(|info| match info {
             FieldInfo::Filter(field) => {
                 let name = field.name();
+                let is_some_resetter = field.filter_some_name().map(|_| {
+                    let is_some_name = field.is_some_name();
+                    quote! {
+                        self.#is_some_name = None;
+                    }
+                });
                 Some(quote! {
                     self.#name = None;
+                    #is_some_resetter
                 })
             }
             FieldInfo::FilterMap(field) => {
                 let name = field.name();
+                let not_name = field.not_name();
+                let is_some_resetter = field.filter_some_name().map(|_| {
+                    let is_some_name = field.is_some_name();
+                    quote! {
+                        self.#is_some_name = None;
+                    }
+                });
+                let all_of_resetter = field.all_of_name().map(|all_of_name| {
+                    quote! {
+                        self.#all_of_name.clear();
+                    }
+                });
                 Some(quote! {
                     self.#name.clear();
+                    self.#not_name.clear();
+                    #all_of_resetter
+                    #is_some_resetter
                 })
             }
             FieldInfo::Key(_) => None,
+            FieldInfo::Sort(field) => field.range_name().map(|range_name| {
+                quote! {
+                    self.#range_name = (::std::ops::Bound::Unbounded, ::std::ops::Bound::Unbounded);
+                }
+            }),
+            FieldInfo::Index(_) => None,
         })
         .collect();
 
@@ -759,6 +3000,9 @@ fn generate_query_struct_impl(
                 let name = field.name();
                 let filter_name: Ident = field.filter_name();
                 let filter_not_name: Ident = field.filter_not_name();
+                let presence_filter = field.filter_some_name().map(|filter_some_name| {
+                    generate_query_presence_filter(&field.is_some_name(), &filter_some_name)
+                });
                 Some(quote! {
                     // Filter by the filter below. Only if it is defined as Some(_).
                     // Using negation if negation is desired, and
@@ -769,6 +3013,8 @@ fn generate_query_struct_impl(
                         Some(false) => filter &= &self.db.#filter_not_name,
                         None => (),
                     };
+
+                    #presence_filter
                 })
             }
             FieldInfo::FilterMap(field) => {
@@ -813,6 +3059,47 @@ fn generate_query_struct_impl(
                         };
                     },
                 };
+                // used to exclude the rows matching any of the excluded values,
+                // rows with an any value are only excluded when excluding the any value itself
+                let not_name = field.not_name();
+                let value_filter_not = match field.filter_any_name() {
+                    Some(filter_any_vec) => quote! {
+                        let mut exclude_any = false;
+                        for value in &self.#not_name {
+                            if ::venndb::Any::is_any(value) {
+                                exclude_any = true;
+                            } else if let Some(index) = self.db.#filter_map_name.get(value) {
+                                exclude |= &self.db.#filter_vec_name[*index];
+                            }
+                        }
+                        exclude.and_not(&self.db.#filter_any_vec);
+                        if exclude_any {
+                            exclude |= &self.db.#filter_any_vec;
+                        }
+                    },
+                    None => quote! {
+                        for value in &self.#not_name {
+                            if let Some(index) = self.db.#filter_map_name.get(value) {
+                                exclude |= &self.db.#filter_vec_name[*index];
+                            }
+                        }
+                    },
+                };
+                let presence_filter = field.filter_some_name().map(|filter_some_name| {
+                    generate_query_presence_filter(&field.is_some_name(), &filter_some_name)
+                });
+                let all_of_filter = field.all_of_name().map(|all_of_name| {
+                    quote! {
+                        // Only match the rows containing all of the given values,
+                        // if any of the values does not occur in any row the search is over.
+                        for value in &self.#all_of_name {
+                            match self.db.#filter_map_name.get(value) {
+                                Some(index) => filter &= &self.db.#filter_vec_name[*index],
+                                None => return None,
+                            };
+                        }
+                    }
+                });
                 // apply the filter
                 Some(quote! {
                     // Filter by the filterm ap below, only if it is defined as Some(_).
@@ -832,21 +3119,230 @@ fn generate_query_struct_impl(
                             filter &= &inter_filter;
                         }
                     }
+
+                    // Exclude the rows matching the values of the negated filter map,
+                    // rows without a value (`None`) are never excluded.
+                    if !self.#not_name.is_empty() {
+                        let mut exclude = ::venndb::__internal::BitVec::repeat(false, self.db.rows.len());
+                        #value_filter_not
+                        filter.and_not(&exclude);
+                    }
+
+                    #all_of_filter
+
+                    #presence_filter
                 })
             }
             FieldInfo::Key(_) => None,
+            FieldInfo::Sort(field) => field.range_name().map(|range_name| {
+                let range_index_name = field.range_index_name();
+                let value = field.range_value(quote! { rows[index] });
+                quote! {
+                    // Filter by the range below, only if at least one of its bounds is set.
+                    // The matching rows are found using the binned range index,
+                    // only checking the rows of the bins containing the bounds one by one.
+                    if !matches!(
+                        self.#range_name,
+                        (::std::ops::Bound::Unbounded, ::std::ops::Bound::Unbounded)
+                    ) {
+                        let rows = &self.db.rows;
+                        let range_filter = self.db.#range_index_name.range(
+                            (self.#range_name.0.as_ref(), self.#range_name.1.as_ref()),
+                            |index| #value,
+                        );
+                        filter &= &range_filter;
+                    }
+                }
+            }),
+            FieldInfo::Index(_) => None,
         })
         .collect();
 
     let name_query_result = format_ident!("{}Result", name_query);
 
+    let name_query_result_kind = format_ident!("{}Kind", name_query_result);
+
+    let query_result_sort_methods: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::Sort(field) => {
+                let sort_name = field.sort_name();
+                let sorted_by_name = field.sorted_by_name();
+                let doc = format!(
+                    "Return an iterator over the found instances of [`{}`], sorted by `{}` (ascending).\n\n\
+                     Instances with equal values are returned in the order they were inserted.",
+                    name,
+                    field.name()
+                );
+                Some(quote! {
+                    #[doc=#doc]
+                    #vis fn #sorted_by_name(&self) -> impl ::std::iter::DoubleEndedIterator<Item = &'a #name> {
+                        let bits = self.bits();
+                        let rows = self.rows;
+                        self.db.#sort_name
+                            .iter()
+                            .filter(move |index| bits.get(**index))
+                            .map(move |index| &rows[*index])
+                    }
+                })
+            }
+            FieldInfo::Key(_) => None,
+            FieldInfo::Filter(_) => None,
+            FieldInfo::FilterMap(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let query_result_filter_map_methods: Vec<_> = fields
+        .iter()
+        .filter_map(|info| match info {
+            FieldInfo::FilterMap(field) => {
+                let ty = field.ty();
+                let facets_name = field.facets_name();
+                let facets_within_name = field.facets_within_name();
+                let facets_doc = format!(
+                    "Return the amount of found instances of [`{}`] for each distinct `{}` value.\n\n\
+                     Values are returned in the order they were first seen, omitting values without any found rows. \
+                     Rows with an _any_ value are counted for every value.",
+                    name,
+                    field.name()
+                );
+                let filter_map_name = field.filter_map_name();
+                let filter_vec_name = field.filter_vec_name();
+                let group_by_name = field.group_by_name();
+                let group_by_doc = format!(
+                    "Group the found instances of [`{}`] by their `{}` value, returning a result for each distinct value.\n\n\
+                     Values are returned in the order they were first seen, omitting values without any found rows. \
+                     Rows with an _any_ value are part of every group.",
+                    name,
+                    field.name()
+                );
+                Some(quote! {
+                    #[doc=#facets_doc]
+                    #vis fn #facets_name(&self) -> ::std::vec::Vec<(&'a #ty, usize)> {
+                        self.db.#facets_within_name(&self.bits())
+                    }
+
+                    #[doc=#group_by_doc]
+                    #vis fn #group_by_name(&self) -> ::std::vec::Vec<(&'a #ty, #name_query_result<'a>)> {
+                        let bits = self.bits();
+                        let mut groups: ::std::vec::Vec<_> = self.db.#filter_map_name
+                            .iter()
+                            .filter_map(|(value, vec_index)| {
+                                let group = &*bits & &self.db.#filter_vec_name[*vec_index];
+                                group.any().then_some((*vec_index, value, group))
+                            })
+                            .collect();
+                        groups.sort_unstable_by_key(|(vec_index, _, _)| *vec_index);
+                        groups
+                            .into_iter()
+                            .map(|(_, value, group)| {
+                                (
+                                    value,
+                                    #name_query_result {
+                                        db: self.db,
+                                        rows: self.rows,
+                                        references: #name_query_result_kind::Bits(group),
+                                    },
+                                )
+                            })
+                            .collect()
+                    }
+                })
+            }
+            FieldInfo::Key(_) => None,
+            FieldInfo::Filter(_) => None,
+            FieldInfo::Sort(_) => None,
+            FieldInfo::Index(_) => None,
+        })
+        .collect();
+
+    let query_result_method_any_weighted = weight.map(|weight| {
+        let weight_name = weight.name();
+        let doc = format!(
+            "Return a random instance of [`{}`] found by the query, selected proportionally to its `{}` weight.",
+            name, weight_name
+        );
+        let doc_with = format!(
+            "Same as `any_weighted`, but using the given source of randomness to select a random instance of [`{}`].",
+            name
+        );
+        let doc_seeded = format!(
+            "Same as `any_weighted`, but deterministically selecting a random instance of [`{}`] using the given seed.",
+            name
+        );
+        // only available together with the thread-local random number generator (`rand` feature)
+        let any_weighted = quote! {
+            ::venndb::__internal::with_rand! {
+                #[doc=#doc]
+                ///
+                /// Rows with a zero, negative, non-finite or missing weight are never selected,
+                /// `None` is returned in case all found rows have such a weight.
+                #vis fn any_weighted(&self) -> Option<&'a #name> {
+                    self.any_weighted_with(&mut ::venndb::__internal::rng())
+                }
+            }
+        };
+        quote! {
+            #any_weighted
+
+            #[doc=#doc_with]
+            ///
+            /// A row is first selected out of all rows in `O(log n)`, accepted if found by the query,
+            /// which is fast for queries matching most of the (weighted) rows. After 16 rejected rows
+            /// it falls back to selecting a row out of the found rows only, in `O(n / 64 + k)`,
+            /// where `k` is the amount of found rows within blocks of 64 rows which are only partially found.
+            #vis fn any_weighted_with<R>(&self, rng: &mut R) -> Option<&'a #name>
+                where
+                    R: ::venndb::RandomSource + ?::std::marker::Sized,
+            {
+                // Fast path: select a row out of all rows using the weight index,
+                // only accepted in case it is found by the query. The accepted rows are still
+                // selected proportionally to their weight, and for queries matching most of the
+                // (weighted) rows this results in a `O(log n)` search instead of a scan of all found rows.
+                let weights = &self.db.weights;
+                let total = weights.total();
+                if total > 0.0 && total.is_finite() {
+                    for _ in 0..16 {
+                        let target = rng.random_unit() * total;
+                        if let Some(index) = weights.find(target) {
+                            if self.contains_index(index) {
+                                return Some(&self.rows[index]);
+                            }
+                        }
+                    }
+                }
+
+                // Slow path: select a row out of the found rows only, scanning the blocks of the
+                // found rows, where only the weights of the rows of partially found blocks are summed.
+                let index = match &self.references {
+                    #name_query_result_kind::Bits(v) => weights.select_ones(v, rng.random_unit()),
+                    #name_query_result_kind::Indices(i) => weights.select_indices(i, rng.random_unit()),
+                }?;
+                Some(&self.rows[index])
+            }
+
+            /// Return `true` if the row at the given index is found by the query.
+            fn contains_index(&self, index: usize) -> bool {
+                match &self.references {
+                    #name_query_result_kind::Bits(v) => v.get(index),
+                    // indices are kept in ascending order
+                    #name_query_result_kind::Indices(i) => i.binary_search(&index).is_ok(),
+                }
+            }
+
+            #[doc=#doc_seeded]
+            #vis fn any_weighted_seeded(&self, seed: u64) -> Option<&'a #name> {
+                self.any_weighted_with(&mut ::venndb::SeededRandom::new(seed))
+            }
+        }
+    });
+
     let name_query_result_doc = format!(
         "Contains a reference to the found instances of [`{}`] if there is at least one found, queried using [`{}`], generated by `#[derive(VennDB)]`.",
         name, name_query
     );
 
-    let name_query_result_kind = format_ident!("{}Kind", name_query_result);
-
     let name_query_result_iter = format_ident!("{}Iter", name_query_result);
 
     let name_query_result_iter_kind = format_ident!("{}Kind", name_query_result_iter);
@@ -864,6 +3360,44 @@ fn generate_query_struct_impl(
         "Return a random instance of [`{}`] found by the query.",
         name
     );
+    let query_result_method_doc_any_with = format!(
+        "Return a random instance of [`{}`] found by the query, using the given source of randomness.",
+        name
+    );
+    let query_result_method_doc_any_seeded = format!(
+        "Return a random instance of [`{}`] found by the query, deterministically selected using the given seed.",
+        name
+    );
+    // only available together with the thread-local random number generator (`rand` feature)
+    let query_result_method_any = quote! {
+        ::venndb::__internal::with_rand! {
+            #[doc=#query_result_method_doc_any]
+            #vis fn any(&self) -> &'a #name {
+                self.any_with(&mut ::venndb::__internal::rng())
+            }
+        }
+    };
+    let query_result_method_doc_sample = format!(
+        "Return up to `n` distinct random instances of [`{}`] found by the query, \
+         selected uniformly without replacement and returned in the order they were inserted.",
+        name
+    );
+    let query_result_method_doc_sample_with = format!(
+        "Same as `sample`, but using the given source of randomness to select the instances of [`{}`].",
+        name
+    );
+    let query_result_method_doc_sample_seeded = format!(
+        "Same as `sample`, but deterministically selecting the instances of [`{}`] using the given seed.",
+        name
+    );
+    let query_result_method_sample = quote! {
+        ::venndb::__internal::with_rand! {
+            #[doc=#query_result_method_doc_sample]
+            #vis fn sample(&self, n: usize) -> ::std::vec::Vec<&'a #name> {
+                self.sample_with(n, &mut ::venndb::__internal::rng())
+            }
+        }
+    };
     let query_result_method_doc_iter = format!(
         "Return an iterator over the instances of [`{}`] found by the query.",
         name
@@ -876,17 +3410,16 @@ fn generate_query_struct_impl(
             /// Reset the query to its initial values.
             #vis fn reset(&mut self) -> &mut Self {
                 #(#filter_resetters)*
+                self.exprs.clear();
                 self
             }
 
             /// Execute the query on the database, returning an iterator over the results.
             #vis fn execute(&self) -> Option<#name_query_result<'a>> {
-                let mut filter = ::venndb::__internal::BitVec::repeat(true, self.db.rows.len());
-
-                #(#filters)*
-
+                let filter = self.execute_filter()?;
                 if filter.any() {
                     Some(#name_query_result {
+                        db: self.db,
                         rows: &self.db.rows,
                         references: #name_query_result_kind::Bits(filter),
                     })
@@ -894,11 +3427,39 @@ fn generate_query_struct_impl(
                     None
                 }
             }
+
+            /// Execute the query on the database, returning the amount of matched rows.
+            ///
+            /// This is cheaper than counting the rows of the result returned by [`Self::execute`].
+            #vis fn count(&self) -> usize {
+                self.execute_filter().map(|filter| filter.count_ones()).unwrap_or_default()
+            }
+
+            /// Execute the query on the database, returning `true` if at least one row matched.
+            #vis fn exists(&self) -> bool {
+                self.execute_filter().is_some_and(|filter| filter.any())
+            }
+
+            /// Compute the bitmap of the rows matching all set filters,
+            /// returning `None` early in case it is known that no rows can match.
+            fn execute_filter(&self) -> Option<::venndb::__internal::BitVec> {
+                let mut filter = ::venndb::__internal::BitVec::repeat(true, self.db.rows.len());
+                filter.and_not(&self.db.tombstones);
+
+                #(#filters)*
+
+                for expr in &self.exprs {
+                    filter &= &expr.evaluate(self.db);
+                }
+
+                Some(filter)
+            }
         }
 
         #[doc=#name_query_result_doc]
         #[derive(Debug)]
         #vis struct #name_query_result<'a> {
+            db: &'a #name_db,
             rows: &'a [#name],
             references: #name_query_result_kind,
         }
@@ -919,21 +3480,87 @@ fn generate_query_struct_impl(
                 &self.rows[index]
             }
 
-            #[doc=#query_result_method_doc_any]
-            #vis fn any(&self) -> &'a #name {
+            #query_result_method_any
+
+            #[doc=#query_result_method_doc_any_with]
+            #vis fn any_with<R>(&self, rng: &mut R) -> &'a #name
+                where
+                    R: ::venndb::RandomSource + ?::std::marker::Sized,
+            {
                 let index = match &self.references {
                     #name_query_result_kind::Bits(v) => {
-                        let n = ::venndb::__internal::rand_range(v.count_ones());
+                        let n = rng.random_index(v.count_ones());
                         v.iter_ones().nth(n).unwrap()
                     }
                     #name_query_result_kind::Indices(i) => {
-                        let n = ::venndb::__internal::rand_range(i.len());
+                        let n = rng.random_index(i.len());
                         i[n]
                     }
                 };
                 &self.rows[index]
             }
 
+            #[doc=#query_result_method_doc_any_seeded]
+            #vis fn any_seeded(&self, seed: u64) -> &'a #name {
+                self.any_with(&mut ::venndb::SeededRandom::new(seed))
+            }
+
+            #query_result_method_sample
+
+            #[doc=#query_result_method_doc_sample_with]
+            #vis fn sample_with<R>(&self, n: usize, rng: &mut R) -> ::std::vec::Vec<&'a #name>
+                where
+                    R: ::venndb::RandomSource + ?::std::marker::Sized,
+            {
+                let count = match &self.references {
+                    #name_query_result_kind::Bits(v) => v.count_ones(),
+                    #name_query_result_kind::Indices(i) => i.len(),
+                };
+                if n >= count {
+                    return self.iter().collect();
+                }
+
+                // Floyd's algorithm: select n distinct ranks out of all found rows,
+                // kept sorted such that they can be resolved in a single pass
+                let mut ranks: ::std::vec::Vec<usize> = ::std::vec::Vec::with_capacity(n);
+                for j in (count - n)..count {
+                    let t = rng.random_index(j + 1);
+                    let rank = match ranks.binary_search(&t) {
+                        Ok(_) => j,
+                        Err(_) => t,
+                    };
+                    if let Err(pos) = ranks.binary_search(&rank) {
+                        ranks.insert(pos, rank);
+                    }
+                }
+
+                match &self.references {
+                    #name_query_result_kind::Bits(v) => {
+                        let mut iter = v.iter_ones();
+                        let mut next_rank = 0;
+                        ranks
+                            .into_iter()
+                            .map(|rank| {
+                                let index = iter.nth(rank - next_rank).unwrap();
+                                next_rank = rank + 1;
+                                &self.rows[index]
+                            })
+                            .collect()
+                    }
+                    #name_query_result_kind::Indices(i) => ranks
+                        .into_iter()
+                        .map(|rank| &self.rows[i[rank]])
+                        .collect(),
+                }
+            }
+
+            #[doc=#query_result_method_doc_sample_seeded]
+            #vis fn sample_seeded(&self, n: usize, seed: u64) -> ::std::vec::Vec<&'a #name> {
+                self.sample_with(n, &mut ::venndb::SeededRandom::new(seed))
+            }
+
+            #query_result_method_any_weighted
+
             #[doc=#query_result_method_doc_iter]
             #vis fn iter(&self) -> #name_query_result_iter<'a, '_> {
                 #name_query_result_iter {
@@ -945,6 +3572,28 @@ fn generate_query_struct_impl(
                 }
             }
 
+            /// Return an iterator over at most `limit` found instances,
+            /// skipping the first `offset` found instances.
+            ///
+            /// The skipped instances are not visited one by one,
+            /// making it efficient to page through large results.
+            #vis fn page(&self, offset: usize, limit: usize) -> ::std::iter::Take<#name_query_result_iter<'a, '_>> {
+                let indices = match &self.references {
+                    #name_query_result_kind::Bits(v) => match v.select(offset) {
+                        Some(index) => #name_query_result_iter_kind::Bits(v.iter_ones_from(index)),
+                        None => #name_query_result_iter_kind::Indices([].iter()),
+                    },
+                    #name_query_result_kind::Indices(i) => {
+                        #name_query_result_iter_kind::Indices(i.get(offset..).unwrap_or_default().iter())
+                    }
+                };
+                #name_query_result_iter {
+                    rows: self.rows,
+                    indices,
+                }
+                .take(limit)
+            }
+
             /// Filter the found results with the given predicate.
             #vis fn filter<F>(&self, predicate: F) -> Option<#name_query_result<'a>>
                 where
@@ -960,10 +3609,29 @@ fn generate_query_struct_impl(
                 }
 
                 Some(#name_query_result {
+                    db: self.db,
                     rows: self.rows,
                     references: #name_query_result_kind::Indices(indices),
                 })
             }
+
+            /// Return the bitmap of the found rows.
+            fn bits(&self) -> ::std::borrow::Cow<'_, ::venndb::__internal::BitVec> {
+                match &self.references {
+                    #name_query_result_kind::Bits(v) => ::std::borrow::Cow::Borrowed(v),
+                    #name_query_result_kind::Indices(i) => {
+                        let mut v = ::venndb::__internal::BitVec::repeat(false, self.rows.len());
+                        for index in i {
+                            v.set(*index, true);
+                        }
+                        ::std::borrow::Cow::Owned(v)
+                    }
+                }
+            }
+
+            #(#query_result_filter_map_methods)*
+
+            #(#query_result_sort_methods)*
         }
 
         #[doc=#name_query_result_iter_doc]
@@ -982,6 +3650,14 @@ fn generate_query_struct_impl(
                 };
                 maybe_index.map(|index| &self.rows[index])
             }
+
+            fn nth(&mut self, n: usize) -> Option<Self::Item> {
+                let maybe_index = match &mut self.indices {
+                    #name_query_result_iter_kind::Bits(v) => v.nth(n),
+                    #name_query_result_iter_kind::Indices(i) => i.nth(n).cloned(),
+                };
+                maybe_index.map(|index| &self.rows[index])
+            }
         }
 
         #vis enum #name_query_result_iter_kind<'a> {
@@ -1013,13 +3689,13 @@ impl ToTokens for DbErrorKind {
         match self {
             Self::DuplicateKey => {
                 tokens.extend(quote! {
-                    /// The error kind for when the row to be insired has a key already known (key duplicate).
+                    /// The error kind for when the row to be inserted or updated has a key already known (key duplicate).
                     DuplicateKey,
                 });
             }
             Self::InvalidRow => {
                 tokens.extend(quote! {
-                    /// The error kind for when the row to be inserted is invalid.
+                    /// The error kind for when the row to be inserted or updated is invalid.
                     InvalidRow,
                 });
             }
@@ -1125,15 +3801,15 @@ impl DbError {
         let error_kinds = &self.error_kinds;
 
         let doc_error_kind = format!(
-            "The kind of error that occurred when appending a row to the [`{}`].",
+            "The kind of error that occurred when appending, updating or upserting a row in the [`{}`].",
             name_db
         );
         let doc_error = format!(
-            "The error type that can be returned when appending a row to the [`{}`].",
+            "The error type that can be returned when appending, updating or upserting a row in the [`{}`].",
             name_db
         );
         let doc_error_kind_method = format!(
-            "The [`{}`] that occurred when appending a row to the [`{}`].",
+            "The [`{}`] that occurred when appending, updating or upserting a row in the [`{}`].",
             ident_error_kind, name_db
         );
 
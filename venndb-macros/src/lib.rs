@@ -6,11 +6,25 @@ mod generate_db;
 mod parse_attrs;
 
 use errors::Errors;
-use field::StructField;
-use parse_attrs::{FieldAttrs, TypeAttrs};
+use field::{FieldInfo, KeyField, KeyPart, StructField};
+use parse_attrs::{CompositeKeyAttr, FieldAttrs, TypeAttrs};
 use proc_macro2::TokenStream;
 use quote::{ToTokens, format_ident, quote};
 
+/// The names of the methods and fields which are part of every generated query.
+const QUERY_NAMES: &[&str] = &[
+    "db",
+    "exprs",
+    "new",
+    "reset",
+    "execute",
+    "execute_filter",
+    "count",
+    "exists",
+    "expr",
+    "signature",
+];
+
 /// Derive macro generating VennDB functionality for this struct.
 ///
 /// See <https://docs.rs/venndb> for more information on how to use it.
@@ -78,7 +92,7 @@ fn impl_from_args_struct(
         }
     };
 
-    let fields: Vec<_> = fields
+    let mut fields: Vec<_> = fields
         .named
         .iter()
         .filter_map(|field| {
@@ -87,6 +101,93 @@ fn impl_from_args_struct(
         })
         .collect();
 
+    // the setters generated on the query for the fields cannot clash with each other,
+    // nor with the methods and fields which are always part of the query
+    let mut query_names: Vec<syn::Ident> = QUERY_NAMES
+        .iter()
+        .map(|name| format_ident!("{}", name))
+        .collect();
+    for field in &mut fields {
+        let names = field
+            .info()
+            .map(|info| info.query_names())
+            .unwrap_or_default();
+        if let Some(name) = names.iter().find(|name| query_names.contains(name)) {
+            errors.err(
+                field.name(),
+                &format!(
+                    "Invalid field-level `venndb` attribute\n\
+                     The query method `{name}` generated for this field clashes with another method of the query",
+                ),
+            );
+            // not indexed, such that the error is the only one reported for it
+            field.skip();
+            continue;
+        }
+        query_names.extend(names);
+    }
+
+    for field in fields
+        .iter()
+        .filter(|field| field.weight().is_some())
+        .skip(1)
+    {
+        errors.err(
+            field.ty(),
+            concat!(
+                "Invalid field-level `venndb` attribute\n",
+                "Only one field can be marked as `weight`",
+            ),
+        );
+    }
+
+    let mut keys: Vec<KeyField> = Vec::with_capacity(type_attrs.keys.len());
+    for key in &type_attrs.keys {
+        let Some(key_field) = composite_key(errors, &fields, key) else {
+            continue;
+        };
+        // the name of a composite key is the names of its fields joined by `_`
+        let name = key_field.name();
+        if fields.iter().any(|field| field.name() == name)
+            || keys.iter().any(|other| other.name() == name)
+        {
+            errors.err(
+                &key.meta,
+                concat!(
+                    "Invalid struct-level `venndb` attribute\n",
+                    "Conflicting key name: the name of a composite key (its fields joined by `_`) ",
+                    "has to be unique among the fields and composite keys",
+                ),
+            );
+            continue;
+        }
+        keys.push(key_field);
+    }
+
+    // the names taken by the fields, the composite keys and the setters generated on the query,
+    // as well as the methods and fields which are always part of the query
+    let mut taken = query_names;
+    taken.extend(fields.iter().map(|field| field.name().clone()));
+    taken.extend(keys.iter().map(|key| key.name().clone()));
+
+    let mut filters = Vec::with_capacity(type_attrs.filters.len());
+    for filter in &type_attrs.filters {
+        let names = FieldInfo::computed(filter).query_names();
+        if names.iter().any(|name| taken.contains(name)) {
+            errors.err(
+                &filter.name,
+                concat!(
+                    "Invalid struct-level `venndb` attribute\n",
+                    "The name of a computed filter (and the query methods generated for it) ",
+                    "has to be unique among the fields, composite keys, computed filters and query methods",
+                ),
+            );
+            continue;
+        }
+        taken.extend(names);
+        filters.push(filter);
+    }
+
     let name_db = match &type_attrs.name {
         Some(name) => format_ident!("{}", name.value()),
         None => format_ident!("{}DB", name),
@@ -96,11 +197,73 @@ fn impl_from_args_struct(
         name,
         &name_db,
         type_attrs.validator.as_ref(),
+        type_attrs.clone.as_ref(),
         vis,
         &fields[..],
+        &keys[..],
+        &filters[..],
     );
 
     quote! {
         #db_code
     }
 }
+
+/// Resolve the fields of a composite key, defined as `#[venndb(key(a, b))]` on the struct.
+fn composite_key<'a>(
+    errors: &Errors,
+    fields: &[StructField<'a>],
+    key: &CompositeKeyAttr,
+) -> Option<KeyField<'a>> {
+    let names = &key.fields;
+    if names.len() < 2 || names.len() > 4 {
+        errors.err(
+            &key.meta,
+            concat!(
+                "Invalid struct-level `venndb` attribute\n",
+                "Composite keys consist of two up to four fields",
+            ),
+        );
+        return None;
+    }
+
+    let mut parts = Vec::with_capacity(names.len());
+    for (index, name) in names.iter().enumerate() {
+        if names[..index].contains(name) {
+            errors.err(
+                name,
+                concat!(
+                    "Invalid struct-level `venndb` attribute\n",
+                    "Duplicate field in composite key",
+                ),
+            );
+            return None;
+        }
+        let Some(field) = fields.iter().find(|field| field.name() == name) else {
+            errors.err(
+                name,
+                concat!(
+                    "Invalid struct-level `venndb` attribute\n",
+                    "Unknown field in composite key",
+                ),
+            );
+            return None;
+        };
+        if field.is_optional() {
+            errors.err(
+                name,
+                concat!(
+                    "Invalid struct-level `venndb` attribute\n",
+                    "Fields of a composite key cannot be `Option`",
+                ),
+            );
+            return None;
+        }
+        parts.push(KeyPart {
+            name: field.name(),
+            ty: field.ty(),
+        });
+    }
+
+    Some(KeyField::composite(parts))
+}
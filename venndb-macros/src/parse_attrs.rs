@@ -7,12 +7,18 @@ use crate::errors::Errors;
 pub struct FieldAttrs<'a> {
     pub kind: Option<FieldKind>,
     pub option_ty: Option<&'a syn::Type>,
+    /// The element type of a collection (`Vec`, `HashSet` or `BTreeSet`) filter,
+    /// only defined for filters marked as multi-valued using `#[venndb(filter(multi))]`.
+    pub collection_ty: Option<&'a syn::Type>,
 }
 
 pub enum FieldKind {
     Key,
     Filter,
     FilterMap { any: bool },
+    Weight,
+    Sort { range: bool },
+    Index,
 }
 
 impl<'a> FieldAttrs<'a> {
@@ -22,7 +28,12 @@ impl<'a> FieldAttrs<'a> {
         let mut skipped = false;
         let mut is_key = false;
         let mut is_filter = false;
+        let mut is_multi = false;
         let mut is_any = false;
+        let mut is_weight = false;
+        let mut is_sort = false;
+        let mut is_range = false;
+        let mut is_index = false;
 
         for attr in &field.attrs {
             let ml: Vec<_> = if let Some(ml) = venndb_attr_to_meta_list(errors, attr) {
@@ -68,6 +79,9 @@ impl<'a> FieldAttrs<'a> {
                             );
                         } else {
                             is_filter = true;
+                            if let syn::Meta::List(ml) = &meta {
+                                is_multi |= parse_filter_args(errors, ml);
+                            }
                         }
                     } else if name.is_ident("any") {
                         if is_key {
@@ -81,6 +95,14 @@ impl<'a> FieldAttrs<'a> {
                         } else {
                             is_any = true;
                         }
+                    } else if name.is_ident("weight") {
+                        is_weight = true;
+                    } else if name.is_ident("sort") {
+                        is_sort = true;
+                    } else if name.is_ident("range") {
+                        is_range = true;
+                    } else if name.is_ident("index") {
+                        is_index = true;
                     } else {
                         errors.err(
                             &meta,
@@ -98,20 +120,47 @@ impl<'a> FieldAttrs<'a> {
 
         if skipped {
             this.kind = None;
+        } else if is_weight {
+            let others = [
+                ("key", is_key),
+                ("filter", is_filter),
+                ("any", is_any),
+                ("sort", is_sort),
+                ("range", is_range),
+                ("index", is_index),
+            ];
+            if !err_combined(errors, field, "weight", &others) {
+                this.kind = Some(FieldKind::Weight);
+            }
+        } else if is_sort || is_range {
+            let name = if is_range { "range" } else { "sort" };
+            let others = [
+                ("key", is_key),
+                ("filter", is_filter),
+                ("any", is_any),
+                ("index", is_index),
+            ];
+            if !err_combined(errors, field, name, &others) {
+                // range filters are served by the sorted rows
+                this.kind = Some(FieldKind::Sort { range: is_range });
+            }
+        } else if is_index {
+            let others = [("key", is_key), ("filter", is_filter), ("any", is_any)];
+            if !err_combined(errors, field, "index", &others) {
+                this.kind = Some(FieldKind::Index);
+            }
         } else if is_key {
-            if this.option_ty.is_some() {
+            this.kind = Some(FieldKind::Key);
+        } else if is_bool(this.option_ty.unwrap_or(&field.ty)) {
+            if is_multi {
                 errors.err(
                     &field.ty,
                     concat!(
                         "Invalid field-level `venndb` attribute\n",
-                        "`key` fields cannot be `Option`",
+                        "`multi` cannot be used with `bool`",
                     ),
                 );
-            } else {
-                this.kind = Some(FieldKind::Key);
-            }
-        } else if is_bool(this.option_ty.unwrap_or(&field.ty)) {
-            if is_any {
+            } else if is_any {
                 errors.err(
                     &field.ty,
                     concat!(
@@ -124,7 +173,44 @@ impl<'a> FieldAttrs<'a> {
             }
         } else if is_filter {
             // bool filters are to be seen as regular filters, even when made explicitly so!
-            this.kind = Some(FieldKind::FilterMap { any: is_any });
+            // collections are only multi-valued when marked as such,
+            // otherwise the collection as a whole is a single filter map value
+            let collection_ty = is_multi
+                .then(|| {
+                    ty_inner(
+                        &["Vec", "HashSet", "BTreeSet"],
+                        this.option_ty.unwrap_or(&field.ty),
+                    )
+                })
+                .flatten();
+            if is_multi && collection_ty.is_none() {
+                errors.err(
+                    &field.ty,
+                    concat!(
+                        "Invalid field-level `venndb` attribute\n",
+                        "`multi` can only be used with `Vec<T>`, `HashSet<T>` or `BTreeSet<T>` filters",
+                    ),
+                );
+            } else if collection_ty.is_some() && this.option_ty.is_some() {
+                errors.err(
+                    &field.ty,
+                    concat!(
+                        "Invalid field-level `venndb` attribute\n",
+                        "collection filters cannot be `Option`, use an empty collection instead",
+                    ),
+                );
+            } else if collection_ty.is_some() && is_any {
+                errors.err(
+                    &field.ty,
+                    concat!(
+                        "Invalid field-level `venndb` attribute\n",
+                        "`any` cannot be used with collection filters",
+                    ),
+                );
+            } else {
+                this.collection_ty = collection_ty;
+                this.kind = Some(FieldKind::FilterMap { any: is_any });
+            }
         } else if is_any {
             errors.err(
                 &field.ty,
@@ -139,7 +225,32 @@ impl<'a> FieldAttrs<'a> {
     }
 }
 
-fn is_bool(ty: &syn::Type) -> bool {
+/// Parse the arguments of a field-level `#[venndb(filter(...))]` attribute,
+/// returning `true` in case the filter is marked as multi-valued.
+fn parse_filter_args(errors: &Errors, ml: &syn::MetaList) -> bool {
+    let Some(args) = errors.ok(ml.parse_args_with(
+        syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated,
+    )) else {
+        return false;
+    };
+    let mut is_multi = false;
+    for arg in args {
+        if arg == "multi" {
+            is_multi = true;
+        } else {
+            errors.err(
+                &arg,
+                concat!(
+                    "Invalid field-level `venndb` attribute\n",
+                    "Expected `multi` as argument of `filter`",
+                ),
+            );
+        }
+    }
+    is_multi
+}
+
+pub fn is_bool(ty: &syn::Type) -> bool {
     if let syn::Type::Path(syn::TypePath { path, .. }) = ty {
         path.is_ident("bool")
     } else {
@@ -158,6 +269,125 @@ fn is_bool(ty: &syn::Type) -> bool {
 pub struct TypeAttrs {
     pub name: Option<syn::LitStr>,
     pub validator: Option<syn::Path>,
+    /// Set using `#[venndb(clone)]`, for structs implementing `Clone`.
+    pub clone: Option<syn::Path>,
+    pub keys: Vec<CompositeKeyAttr>,
+    pub filters: Vec<ComputedFilterAttr>,
+}
+
+/// A composite key defined as `#[venndb(key(a, b))]` on the struct.
+pub struct CompositeKeyAttr {
+    pub meta: syn::MetaList,
+    pub fields: Vec<syn::Ident>,
+}
+
+/// A computed filter defined as `#[venndb(filter(name = "x", with = f))]` on the struct.
+pub struct ComputedFilterAttr {
+    pub name: syn::Ident,
+    pub with: syn::Path,
+    /// The type returned by `with`, a `bool` filter in case it is not defined.
+    pub ty: Option<syn::Type>,
+    /// Set using the `multi` argument, for a collection `ty` of which each value is registered.
+    pub multi: bool,
+}
+
+/// A single `<arg> = <value>` (or `multi`) argument of a computed filter.
+enum ComputedFilterArg {
+    Name(syn::LitStr),
+    With(syn::Path),
+    Ty(syn::Type),
+    Multi,
+}
+
+impl syn::parse::Parse for ComputedFilterArg {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let arg: syn::Ident = input.parse()?;
+        if arg == "multi" {
+            return Ok(Self::Multi);
+        }
+        input.parse::<syn::Token![=]>()?;
+        if arg == "name" {
+            Ok(Self::Name(input.parse()?))
+        } else if arg == "with" {
+            Ok(Self::With(input.parse()?))
+        } else if arg == "ty" {
+            Ok(Self::Ty(input.parse()?))
+        } else {
+            Err(syn::Error::new(
+                arg.span(),
+                concat!(
+                    "Invalid struct-level `venndb` attribute\n",
+                    "Expected one of: `name`, `with`, `ty` or `multi`",
+                ),
+            ))
+        }
+    }
+}
+
+impl ComputedFilterAttr {
+    fn parse(errors: &Errors, ml: &syn::MetaList) -> Option<Self> {
+        let args = errors.ok(ml.parse_args_with(
+            syn::punctuated::Punctuated::<ComputedFilterArg, syn::Token![,]>::parse_terminated,
+        ))?;
+
+        let (mut name, mut with, mut ty, mut multi) = (None, None, None, false);
+        for arg in args {
+            match arg {
+                ComputedFilterArg::Name(lit) => name = errors.ok(lit.parse::<syn::Ident>()),
+                ComputedFilterArg::With(path) => with = Some(path),
+                ComputedFilterArg::Ty(t) => ty = Some(t),
+                ComputedFilterArg::Multi => multi = true,
+            }
+        }
+
+        let (Some(name), Some(with)) = (name, with) else {
+            errors.err(
+                ml,
+                concat!(
+                    "Invalid struct-level `venndb` attribute\n",
+                    "Computed filters require both a `name` and a `with` function",
+                ),
+            );
+            return None;
+        };
+
+        if multi {
+            let option_ty = ty.as_ref().and_then(|ty| ty_inner(&["Option"], ty));
+            let collection_ty = ty
+                .as_ref()
+                .and_then(|ty| ty_inner(&["Vec", "HashSet", "BTreeSet"], option_ty.unwrap_or(ty)));
+            if collection_ty.is_none() {
+                let msg = concat!(
+                    "Invalid struct-level `venndb` attribute\n",
+                    "`multi` can only be used with a `Vec<K>`, `HashSet<K>` or `BTreeSet<K>` type",
+                );
+                match &ty {
+                    Some(ty) => errors.err(ty, msg),
+                    None => errors.err(ml, msg),
+                }
+                return None;
+            }
+            if let Some(ty) = &ty
+                && option_ty.is_some()
+            {
+                errors.err(
+                    ty,
+                    concat!(
+                        "Invalid struct-level `venndb` attribute\n",
+                        "collection filters cannot be `Option`, use an empty collection instead",
+                    ),
+                );
+                return None;
+            }
+        }
+
+        Some(Self {
+            name,
+            with,
+            ty,
+            multi,
+        })
+    }
 }
 
 impl TypeAttrs {
@@ -182,6 +412,25 @@ impl TypeAttrs {
                     if let Some(m) = errors.expect_meta_name_value(&meta) {
                         this.validator = errors.expect_path(&m.value).cloned();
                     }
+                } else if name.is_ident("clone") {
+                    this.clone = errors.expect_meta_word(&meta).cloned();
+                } else if name.is_ident("key") {
+                    if let Some(ml) = errors.expect_meta_list(&meta)
+                        && let Some(fields) = errors.ok(ml.parse_args_with(
+                            syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated,
+                        ))
+                    {
+                        this.keys.push(CompositeKeyAttr {
+                            meta: ml.clone(),
+                            fields: fields.into_iter().collect(),
+                        });
+                    }
+                } else if name.is_ident("filter") {
+                    if let Some(ml) = errors.expect_meta_list(&meta)
+                        && let Some(filter) = ComputedFilterAttr::parse(errors, ml)
+                    {
+                        this.filters.push(filter);
+                    }
                 } else {
                     errors.err(
                         &meta,
@@ -198,6 +447,23 @@ impl TypeAttrs {
     }
 }
 
+/// Reports an error if the given field-level attribute is combined with any of the other
+/// (incompatible) attributes set on the field, returning whether it is.
+fn err_combined(errors: &Errors, field: &syn::Field, name: &str, others: &[(&str, bool)]) -> bool {
+    match others.iter().find(|(_, is_set)| *is_set) {
+        Some((other, _)) => {
+            errors.err(
+                &field.ty,
+                &format!(
+                    "Invalid field-level `venndb` attribute\n`{name}` cannot be combined with `{other}`"
+                ),
+            );
+            true
+        }
+        None => false,
+    }
+}
+
 /// Filters out non-`#[venndb(...)]` attributes and converts to a sequence of `syn::Meta`.
 fn venndb_attr_to_meta_list(
     errors: &Errors,
@@ -223,7 +489,7 @@ fn is_venndb_attr(attr: &syn::Attribute) -> bool {
 }
 
 /// Returns `Some(T)` if a type is `wrapper_name<T>` for any `wrapper_name` in `wrapper_names`.
-fn ty_inner<'a>(wrapper_names: &[&str], ty: &'a syn::Type) -> Option<&'a syn::Type> {
+pub fn ty_inner<'a>(wrapper_names: &[&str], ty: &'a syn::Type) -> Option<&'a syn::Type> {
     if let syn::Type::Path(path) = ty {
         if path.qself.is_some() {
             return None;
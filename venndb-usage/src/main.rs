@@ -1436,3 +1436,1856 @@ mod tests_v0_5 {
         assert_eq!(results[1].id, 3);
     }
 }
+
+#[cfg(test)]
+mod tests_v0_7 {
+    use super::*;
+
+    #[derive(Debug, Clone, VennDB)]
+    #[venndb(validator = proxy_validator, clone)]
+    pub struct Proxy {
+        #[venndb(key)]
+        id: u32,
+        #[venndb(key)]
+        address: String,
+        is_active: bool,
+        is_mobile: Option<bool>,
+        #[venndb(filter, any)]
+        country: Option<Country>,
+        requests: u64,
+    }
+
+    fn proxy_validator(proxy: &Proxy) -> bool {
+        !proxy.address.is_empty()
+    }
+
+    #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+    pub enum Country {
+        Any,
+        BE,
+        NL,
+        US,
+    }
+
+    impl Any for Country {
+        fn is_any(&self) -> bool {
+            self == &Country::Any
+        }
+    }
+
+    #[derive(Debug, Clone, VennDB)]
+    #[venndb(clone)]
+    pub struct WeightedNode {
+        #[venndb(key)]
+        id: u32,
+        is_active: bool,
+        #[venndb(weight)]
+        capacity: u32,
+    }
+
+    fn weighted_node_db(capacities: &[u32]) -> WeightedNodeDB {
+        WeightedNodeDB::from_iter(
+            capacities
+                .iter()
+                .enumerate()
+                .map(|(i, capacity)| WeightedNode {
+                    id: i as u32,
+                    is_active: true,
+                    capacity: *capacity,
+                }),
+        )
+        .unwrap()
+    }
+
+    #[derive(Debug, Clone, VennDB)]
+    #[venndb(clone)]
+    pub struct Server {
+        #[venndb(key)]
+        id: u32,
+        is_active: bool,
+        #[venndb(sort, range)]
+        latency: u32,
+        #[venndb(range)]
+        priority: Option<u8>,
+    }
+
+    fn server_db() -> ServerDB {
+        ServerDB::from_rows(vec![
+            Server {
+                id: 1,
+                is_active: true,
+                latency: 300,
+                priority: Some(2),
+            },
+            Server {
+                id: 2,
+                is_active: false,
+                latency: 100,
+                priority: None,
+            },
+            Server {
+                id: 3,
+                is_active: true,
+                latency: 200,
+                priority: Some(1),
+            },
+            Server {
+                id: 4,
+                is_active: true,
+                latency: 100,
+                priority: Some(2),
+            },
+        ])
+        .unwrap()
+    }
+
+    fn server_ids<'a>(iter: impl Iterator<Item = &'a Server>) -> Vec<u32> {
+        iter.map(|server| server.id).collect()
+    }
+
+    #[derive(Debug, Clone, VennDB)]
+    #[venndb(clone)]
+    pub struct Route {
+        #[venndb(key)]
+        id: u32,
+        #[venndb(index)]
+        upstream: String,
+        #[venndb(index)]
+        region: Option<String>,
+    }
+
+    fn route(id: u32, upstream: &str, region: Option<&str>) -> Route {
+        Route {
+            id,
+            upstream: upstream.to_owned(),
+            region: region.map(ToOwned::to_owned),
+        }
+    }
+
+    fn route_ids<'a>(iter: impl Iterator<Item = &'a Route>) -> Vec<u32> {
+        iter.map(|route| route.id).collect()
+    }
+
+    #[derive(Debug, Clone, VennDB)]
+    #[venndb(key(host, port), clone)]
+    pub struct Endpoint {
+        #[venndb(key)]
+        id: u32,
+        host: String,
+        port: u16,
+        is_active: bool,
+        requests: u64,
+    }
+
+    #[derive(Debug, Clone, VennDB)]
+    #[venndb(clone)]
+    pub struct Account {
+        #[venndb(key)]
+        id: u32,
+        #[venndb(key)]
+        external_id: Option<String>,
+        is_active: bool,
+    }
+
+    fn account(id: u32, external_id: Option<&str>) -> Account {
+        Account {
+            id,
+            external_id: external_id.map(ToOwned::to_owned),
+            is_active: true,
+        }
+    }
+
+    #[derive(Debug, Clone, VennDB)]
+    #[venndb(clone)]
+    pub struct Gateway {
+        #[venndb(key)]
+        id: u32,
+        #[venndb(filter(multi))]
+        protocols: Vec<String>,
+        #[venndb(filter(multi))]
+        labels: std::collections::BTreeSet<String>,
+    }
+
+    fn gateway(id: u32, protocols: &[&str], labels: &[&str]) -> Gateway {
+        Gateway {
+            id,
+            protocols: protocols.iter().map(|p| p.to_string()).collect(),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[derive(Debug, VennDB)]
+    pub struct Chain {
+        #[venndb(key)]
+        id: u32,
+        // not marked as `multi`, such that the collection is a single value
+        #[venndb(filter)]
+        hops: Vec<String>,
+    }
+
+    fn chain(id: u32, hops: &[&str]) -> Chain {
+        Chain {
+            id,
+            hops: hops.iter().map(|hop| hop.to_string()).collect(),
+        }
+    }
+
+    fn gateway_ids(query: &GatewayDBQuery) -> Vec<u32> {
+        query
+            .execute()
+            .map(|result| result.iter().map(|gateway| gateway.id).collect())
+            .unwrap_or_default()
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub enum LatencyBucket {
+        Low,
+        Medium,
+        High,
+    }
+
+    #[derive(Debug, Clone, VennDB)]
+    #[venndb(clone)]
+    #[venndb(filter(name = "is_fast", with = peer_is_fast))]
+    #[venndb(filter(name = "latency_bucket", with = peer_latency_bucket, ty = LatencyBucket))]
+    #[venndb(filter(name = "is_premium", with = peer_is_premium, ty = Option<bool>))]
+    pub struct Peer {
+        #[venndb(key)]
+        id: u32,
+        latency: u32,
+        tier: Option<u8>,
+    }
+
+    fn peer_is_fast(peer: &Peer) -> bool {
+        peer.latency < 100
+    }
+
+    fn peer_latency_bucket(peer: &Peer) -> LatencyBucket {
+        match peer.latency {
+            0..100 => LatencyBucket::Low,
+            100..500 => LatencyBucket::Medium,
+            _ => LatencyBucket::High,
+        }
+    }
+
+    fn peer_is_premium(peer: &Peer) -> Option<bool> {
+        peer.tier.map(|tier| tier >= 3)
+    }
+
+    fn peer_ids(query: &PeerDBQuery) -> Vec<u32> {
+        query
+            .execute()
+            .map(|result| result.iter().map(|peer| peer.id).collect())
+            .unwrap_or_default()
+    }
+
+    fn endpoint(id: u32, host: &str, port: u16) -> Endpoint {
+        Endpoint {
+            id,
+            host: host.to_owned(),
+            port,
+            is_active: true,
+            requests: 0,
+        }
+    }
+
+    fn proxy_db() -> ProxyDB {
+        ProxyDB::from_rows(vec![
+            Proxy {
+                id: 1,
+                address: "10.0.0.1".to_string(),
+                is_active: true,
+                is_mobile: Some(true),
+                country: Some(Country::BE),
+                requests: 0,
+            },
+            Proxy {
+                id: 2,
+                address: "10.0.0.2".to_string(),
+                is_active: true,
+                is_mobile: None,
+                country: Some(Country::Any),
+                requests: 0,
+            },
+            Proxy {
+                id: 3,
+                address: "10.0.0.3".to_string(),
+                is_active: false,
+                is_mobile: Some(false),
+                country: None,
+                requests: 0,
+            },
+            Proxy {
+                id: 4,
+                address: "10.0.0.4".to_string(),
+                is_active: true,
+                is_mobile: Some(false),
+                country: Some(Country::NL),
+                requests: 0,
+            },
+        ])
+        .unwrap()
+    }
+
+    fn proxy_ids(result: Option<ProxyDBQueryResult>) -> Vec<u32> {
+        result
+            .map(|result| result.iter().map(|proxy| proxy.id).collect())
+            .unwrap_or_default()
+    }
+
+    fn employee_db() -> EmployeeDB {
+        EmployeeDB::from_rows(vec![
+            Employee {
+                id: 1,
+                name: "Alice".to_string(),
+                is_manager: true,
+                is_admin: false,
+                is_active: true,
+                department: Department::Engineering,
+            },
+            Employee {
+                id: 2,
+                name: "Bob".to_string(),
+                is_manager: false,
+                is_admin: false,
+                is_active: true,
+                department: Department::HR,
+            },
+            Employee {
+                id: 3,
+                name: "Charlie".to_string(),
+                is_manager: true,
+                is_admin: true,
+                is_active: true,
+                department: Department::Sales,
+            },
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_remove_by_key() {
+        let mut db = employee_db();
+
+        assert_eq!(db.get_by_id(&1).unwrap().name, "Alice");
+        assert!(db.remove_by_id(&1));
+        assert!(!db.remove_by_id(&1));
+
+        assert_eq!(db.len(), 2);
+        assert!(db.get_by_id(&1).is_none());
+        assert!(db.get_by_name("Alice").is_none());
+        assert_eq!(
+            db.iter().map(|employee| employee.id).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+
+        let mut query = db.query();
+        query.is_manager(true);
+        let results: Vec<_> = query.execute().unwrap().iter().collect();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, 3);
+        assert_eq!(query.execute().unwrap().any().id, 3);
+
+        // the key is free to be used again
+        db.append(Employee {
+            id: 1,
+            name: "Alice".to_string(),
+            is_manager: false,
+            is_admin: false,
+            is_active: true,
+            department: Department::Engineering,
+        })
+        .unwrap();
+        assert_eq!(db.len(), 3);
+        assert!(!db.get_by_id(&1).unwrap().is_manager);
+    }
+
+    #[test]
+    fn test_remove_at() {
+        let mut db = employee_db();
+
+        assert!(!db.remove_at(3));
+        assert!(db.remove_at(1));
+        assert!(!db.remove_at(1));
+        assert!(db.remove_by_name("Charlie"));
+
+        assert_eq!(db.len(), 1);
+        assert!(!db.is_empty());
+        assert!(db.get_by_name("Bob").is_none());
+
+        let mut query = db.query();
+        assert!(query.department(Department::HR).execute().is_none());
+
+        let rows = db.into_rows();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].id, 1);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut db = employee_db();
+        assert_eq!(db.compact(), EmployeeDBCompactStats::default());
+
+        assert!(db.remove_by_id(&1));
+        assert!(db.remove_by_id(&3));
+
+        let stats = db.compact();
+        assert_eq!(stats.rows_removed, 2);
+        assert_eq!(stats.filter_values_removed, 2);
+        assert_eq!(db.len(), 1);
+        assert_eq!(db.capacity(), 3);
+
+        // key indices are renumbered
+        assert_eq!(db.get_by_id(&2).unwrap().name, "Bob");
+        assert_eq!(db.get_by_name("Bob").unwrap().id, 2);
+        assert!(!db.remove_at(1));
+
+        let mut query = db.query();
+        assert!(
+            query
+                .department(Department::Engineering)
+                .execute()
+                .is_none()
+        );
+        query.reset();
+        assert_eq!(
+            query
+                .department(Department::HR)
+                .execute()
+                .unwrap()
+                .first()
+                .id,
+            2
+        );
+
+        db.append(Employee {
+            id: 4,
+            name: "David".to_string(),
+            is_manager: true,
+            is_admin: false,
+            is_active: true,
+            department: Department::Any,
+        })
+        .unwrap();
+        db.append(Employee {
+            id: 5,
+            name: "Eve".to_string(),
+            is_manager: true,
+            is_admin: false,
+            is_active: false,
+            department: Department::Sales,
+        })
+        .unwrap();
+
+        let mut query = db.query();
+        query.is_manager(true).department(Department::Sales);
+        let results: Vec<_> = query.execute().unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(results, vec![4, 5]);
+
+        // removing the only Sales row keeps the value around
+        // until compaction, even though the any row matches it
+        assert!(db.remove_by_id(&5));
+        let stats = db.compact();
+        assert_eq!(stats.rows_removed, 1);
+        assert_eq!(stats.filter_values_removed, 1);
+
+        let mut query = db.query();
+        query.department(Department::Sales);
+        let results: Vec<_> = query.execute().unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(results, vec![4]);
+        query.reset();
+        query.department(Department::Any);
+        let results: Vec<_> = query.execute().unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(results, vec![4]);
+    }
+
+    #[test]
+    fn test_compact_after_update() {
+        let mut db = proxy_db();
+
+        // the only BE row no longer has that value once updated
+        db.update_by_id(&1, |proxy| proxy.country = Some(Country::NL))
+            .unwrap();
+        let stats = db.compact();
+        assert_eq!(stats.rows_removed, 0);
+        assert_eq!(stats.filter_values_removed, 1);
+        assert_eq!(db.len(), 4);
+
+        let distinct: Vec<_> = db.distinct_country().collect();
+        assert_eq!(distinct, vec![&Country::NL]);
+
+        let mut query = db.query();
+        query.country(Country::NL);
+        assert_eq!(proxy_ids(query.execute()), vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn test_update_by_key() {
+        let mut db = proxy_db();
+
+        let proxy = db
+            .update_by_id(&1, |proxy| {
+                proxy.is_active = false;
+                proxy.is_mobile = None;
+                proxy.country = Some(Country::US);
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(proxy.country, Some(Country::US));
+        assert!(
+            db.update_by_id(&42, |proxy| proxy.is_active = true)
+                .unwrap()
+                .is_none()
+        );
+
+        let mut query = db.query();
+        query.is_active(true);
+        assert_eq!(proxy_ids(query.execute()), vec![2, 4]);
+
+        let mut query = db.query();
+        query.is_mobile(true);
+        assert!(query.execute().is_none());
+
+        let mut query = db.query();
+        query.country(Country::BE);
+        assert_eq!(proxy_ids(query.execute()), vec![2]);
+        query.reset();
+        query.country(Country::US);
+        assert_eq!(proxy_ids(query.execute()), vec![1, 2]);
+
+        // a row can become (or stop being) an any row
+        db.update_by_address("10.0.0.2", |proxy| proxy.country = None)
+            .unwrap()
+            .unwrap();
+        db.update_by_id(&3, |proxy| proxy.country = Some(Country::Any))
+            .unwrap()
+            .unwrap();
+        let mut query = db.query();
+        query.country(Country::NL);
+        assert_eq!(proxy_ids(query.execute()), vec![3, 4]);
+
+        // keys are re-indexed
+        db.update_by_id(&4, |proxy| {
+            proxy.id = 40;
+            proxy.address = "10.0.0.40".to_string();
+        })
+        .unwrap()
+        .unwrap();
+        assert!(db.get_by_id(&4).is_none());
+        assert!(db.get_by_address("10.0.0.4").is_none());
+        assert_eq!(db.get_by_id(&40).unwrap().address, "10.0.0.40");
+        assert_eq!(db.get_by_address("10.0.0.40").unwrap().id, 40);
+    }
+
+    #[test]
+    fn test_update_by_key_rollback() {
+        let mut db = proxy_db();
+
+        let err = db
+            .update_by_id(&1, |proxy| {
+                proxy.address = "10.0.0.2".to_string();
+                proxy.is_active = false;
+            })
+            .unwrap_err();
+        assert_eq!(ProxyDBErrorKind::DuplicateKey, err.kind());
+        assert_eq!(err.row_index(), 0);
+        assert!(!err.input().is_active);
+
+        let err = db
+            .update_by_id(&1, |proxy| {
+                proxy.address = String::new();
+                proxy.country = Some(Country::US);
+            })
+            .unwrap_err();
+        assert_eq!(ProxyDBErrorKind::InvalidRow, err.kind());
+
+        let proxy = db.get_by_id(&1).unwrap();
+        assert_eq!(proxy.address, "10.0.0.1");
+        assert!(proxy.is_active);
+        assert_eq!(proxy.country, Some(Country::BE));
+        assert_eq!(db.get_by_address("10.0.0.2").unwrap().id, 2);
+
+        let mut query = db.query();
+        query.country(Country::US);
+        assert_eq!(proxy_ids(query.execute()), vec![2]);
+    }
+
+    #[test]
+    fn test_mutate_unindexed_fields() {
+        let mut db = proxy_db();
+
+        let proxy = db.get_mut_by_id(&1).unwrap();
+        assert_eq!(*proxy.id, 1);
+        assert_eq!(proxy.country, &Some(Country::BE));
+        *proxy.requests += 1;
+        assert!(db.get_mut_by_address("10.0.0.42").is_none());
+
+        assert!(db.remove_by_id(&3));
+        for proxy in db.iter_mut_unindexed() {
+            *proxy.requests += 10;
+        }
+
+        assert_eq!(
+            db.iter().map(|proxy| proxy.requests).collect::<Vec<_>>(),
+            vec![11, 10, 10]
+        );
+    }
+
+    #[test]
+    fn test_upsert() {
+        let mut db = proxy_db();
+
+        let previous = db
+            .upsert(Proxy {
+                id: 1,
+                address: "10.0.0.1".to_string(),
+                is_active: false,
+                is_mobile: Some(true),
+                country: Some(Country::US),
+                requests: 5,
+            })
+            .unwrap()
+            .unwrap();
+        assert!(previous.is_active);
+        assert_eq!(previous.country, Some(Country::BE));
+        assert_eq!(db.len(), 4);
+        assert_eq!(db.get_by_id(&1).unwrap().requests, 5);
+
+        // the replaced row can change its other keys
+        let previous = db
+            .upsert(Proxy {
+                id: 4,
+                address: "10.0.0.44".to_string(),
+                is_active: true,
+                is_mobile: Some(false),
+                country: Some(Country::NL),
+                requests: 0,
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(previous.address, "10.0.0.4");
+        assert!(db.get_by_address("10.0.0.4").is_none());
+        assert_eq!(db.get_by_address("10.0.0.44").unwrap().id, 4);
+
+        assert!(
+            db.upsert(Proxy {
+                id: 5,
+                address: "10.0.0.5".to_string(),
+                is_active: true,
+                is_mobile: None,
+                country: Some(Country::US),
+                requests: 0,
+            })
+            .unwrap()
+            .is_none()
+        );
+        assert_eq!(db.len(), 5);
+
+        let mut query = db.query();
+        query.is_active(true).country(Country::US);
+        assert_eq!(proxy_ids(query.execute()), vec![2, 5]);
+
+        // keys matching different rows
+        let err = db
+            .upsert(Proxy {
+                id: 1,
+                address: "10.0.0.2".to_string(),
+                is_active: true,
+                is_mobile: None,
+                country: None,
+                requests: 0,
+            })
+            .unwrap_err();
+        assert_eq!(ProxyDBErrorKind::DuplicateKey, err.kind());
+
+        // invalid rows
+        let err = db
+            .upsert(Proxy {
+                id: 1,
+                address: String::new(),
+                is_active: true,
+                is_mobile: None,
+                country: None,
+                requests: 0,
+            })
+            .unwrap_err();
+        assert_eq!(ProxyDBErrorKind::InvalidRow, err.kind());
+        let err = db
+            .upsert(Proxy {
+                id: 6,
+                address: String::new(),
+                is_active: true,
+                is_mobile: None,
+                country: None,
+                requests: 0,
+            })
+            .unwrap_err();
+        assert_eq!(ProxyDBErrorKind::InvalidRow, err.kind());
+        assert_eq!(db.len(), 5);
+        assert_eq!(db.get_by_id(&1).unwrap().address, "10.0.0.1");
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut db = proxy_db();
+        assert!(db.remove_by_id(&1));
+
+        let stats = db.retain(|proxy| proxy.is_active);
+        assert_eq!(stats.rows_removed, 2);
+        assert_eq!(stats.filter_values_removed, 1);
+        assert_eq!(db.len(), 2);
+        assert!(db.get_by_id(&3).is_none());
+        assert_eq!(db.get_by_address("10.0.0.4").unwrap().id, 4);
+
+        let mut query = db.query();
+        query.is_mobile(false);
+        assert_eq!(proxy_ids(query.execute()), vec![4]);
+        query.reset();
+        query.country(Country::BE);
+        assert_eq!(proxy_ids(query.execute()), vec![2]);
+
+        assert_eq!(db.retain(|_| true), ProxyDBCompactStats::default());
+        let stats = db.retain(|_| false);
+        assert_eq!(stats.rows_removed, 2);
+        assert!(db.is_empty());
+        assert!(db.query().execute().is_none());
+    }
+
+    #[test]
+    fn test_not_filter_map() {
+        let db = proxy_db();
+
+        let mut query = db.query();
+        query.not_country(Country::BE);
+        // any rows and rows without a country are kept
+        assert_eq!(proxy_ids(query.execute()), vec![2, 3, 4]);
+
+        query.not_country(Country::NL).not_country(Country::US);
+        assert_eq!(proxy_ids(query.execute()), vec![2, 3]);
+
+        query.not_country(Country::Any);
+        assert_eq!(proxy_ids(query.execute()), vec![3]);
+
+        query.reset();
+        query.is_active(true).not_country(Country::Any);
+        assert_eq!(proxy_ids(query.execute()), vec![1, 4]);
+
+        query.reset();
+        query
+            .country(Country::BE)
+            .country(Country::NL)
+            .not_country(Country::NL);
+        assert_eq!(proxy_ids(query.execute()), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_is_none_is_some() {
+        let mut db = proxy_db();
+
+        let mut query = db.query();
+        query.is_mobile_is_none();
+        assert_eq!(proxy_ids(query.execute()), vec![2]);
+
+        query.reset();
+        query.is_mobile_is_some();
+        assert_eq!(proxy_ids(query.execute()), vec![1, 3, 4]);
+
+        query.is_mobile(false);
+        assert_eq!(proxy_ids(query.execute()), vec![3, 4]);
+
+        query.reset();
+        query.country_is_none();
+        assert_eq!(proxy_ids(query.execute()), vec![3]);
+
+        query.is_active(true);
+        assert!(query.execute().is_none());
+
+        query.reset();
+        query.country_is_some();
+        // the any value counts as a value
+        assert_eq!(proxy_ids(query.execute()), vec![1, 2, 4]);
+
+        query.country_is_none().country_is_some();
+        // last call wins
+        assert_eq!(proxy_ids(query.execute()), vec![1, 2, 4]);
+
+        // presence is kept up to date on mutations
+        db.update_by_id(&3, |proxy| proxy.country = Some(Country::US))
+            .unwrap();
+        db.update_by_id(&2, |proxy| proxy.is_mobile = Some(true))
+            .unwrap();
+        db.remove_by_id(&1);
+        db.compact();
+
+        let mut query = db.query();
+        query.country_is_none();
+        assert!(query.execute().is_none());
+
+        query.reset();
+        query.is_mobile_is_some();
+        assert_eq!(proxy_ids(query.execute()), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_query_expr() {
+        let db = proxy_db();
+
+        // mobile OR any country, AND active
+        let mut query = db.query();
+        query.is_active(true).expr(ProxyDBExpr::or(
+            ProxyDBExpr::field_is_mobile(true),
+            ProxyDBExpr::field_country(Country::Any),
+        ));
+        assert_eq!(proxy_ids(query.execute()), vec![1, 2]);
+
+        query.reset();
+        query.expr(ProxyDBExpr::or(
+            ProxyDBExpr::field_country(Country::NL),
+            ProxyDBExpr::field_is_active(false),
+        ));
+        // any rows match all countries
+        assert_eq!(proxy_ids(query.execute()), vec![2, 3, 4]);
+
+        query.reset();
+        query.expr(ProxyDBExpr::not(ProxyDBExpr::field_is_mobile(true)));
+        // rows without a value are matched by the negated expression
+        assert_eq!(proxy_ids(query.execute()), vec![2, 3, 4]);
+
+        query.expr(ProxyDBExpr::and(
+            ProxyDBExpr::field_is_active(true),
+            ProxyDBExpr::not(ProxyDBExpr::field_country(Country::US)),
+        ));
+        // the any row is not excluded by negating another value
+        assert_eq!(proxy_ids(query.execute()), vec![2, 4]);
+
+        query.reset();
+        query.expr(ProxyDBExpr::field_country(Country::US));
+        // only the any row
+        assert_eq!(proxy_ids(query.execute()), vec![2]);
+
+        query.reset();
+        query.expr(ProxyDBExpr::and(
+            ProxyDBExpr::field_is_active(true),
+            ProxyDBExpr::field_is_active(false),
+        ));
+        assert!(query.execute().is_none());
+    }
+
+    #[test]
+    fn test_query_expr_not_any() {
+        let db = proxy_db();
+
+        // negated expressions exclude the same rows as the negated filters of the query
+        for country in [Country::BE, Country::NL, Country::US, Country::Any] {
+            let mut query = db.query();
+            query.not_country(country.clone());
+            let expected = proxy_ids(query.execute());
+
+            query.reset();
+            query.expr(ProxyDBExpr::not(ProxyDBExpr::field_country(country)));
+            assert_eq!(proxy_ids(query.execute()), expected);
+        }
+
+        let mut query = db.query();
+        query.expr(ProxyDBExpr::not(ProxyDBExpr::field_country(Country::BE)));
+        assert_eq!(proxy_ids(query.execute()), vec![2, 3, 4]);
+
+        // rows with an any value match both an expression and its negation
+        query.reset();
+        query.expr(ProxyDBExpr::not(ProxyDBExpr::or(
+            ProxyDBExpr::field_country(Country::BE),
+            ProxyDBExpr::field_country(Country::NL),
+        )));
+        assert_eq!(proxy_ids(query.execute()), vec![2, 3]);
+
+        query.reset();
+        query.expr(ProxyDBExpr::not(ProxyDBExpr::not(
+            ProxyDBExpr::field_country(Country::BE),
+        )));
+        assert_eq!(proxy_ids(query.execute()), vec![1, 2]);
+
+        query.reset();
+        query.expr(ProxyDBExpr::not(ProxyDBExpr::field_country(Country::Any)));
+        assert_eq!(proxy_ids(query.execute()), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_query_expr_removed_rows() {
+        let mut db = proxy_db();
+        db.remove_by_id(&1);
+
+        let mut query = db.query();
+        query.expr(ProxyDBExpr::or(
+            ProxyDBExpr::field_is_mobile(true),
+            ProxyDBExpr::not(ProxyDBExpr::field_is_active(true)),
+        ));
+        assert_eq!(proxy_ids(query.execute()), vec![3]);
+    }
+
+    #[test]
+    fn test_query_count_exists() {
+        let mut db = proxy_db();
+
+        let mut query = db.query();
+        assert_eq!(query.count(), 4);
+        assert!(query.exists());
+
+        query.is_active(true);
+        assert_eq!(query.count(), 3);
+        assert!(query.exists());
+
+        query.country(Country::US);
+        assert_eq!(query.count(), 1);
+
+        query.reset();
+        query.is_mobile(true).is_active(false);
+        assert_eq!(query.count(), 0);
+        assert!(!query.exists());
+
+        db.remove_by_id(&2);
+        let mut query = db.query();
+        assert_eq!(query.count(), 3);
+        query.country(Country::US);
+        assert_eq!(query.count(), 0);
+        assert!(!query.exists());
+    }
+
+    #[test]
+    fn test_any_weighted() {
+        let db = weighted_node_db(&[0, 0, 5, 0]);
+        for _ in 0..100 {
+            let node = db.query().execute().unwrap().any_weighted().unwrap();
+            assert_eq!(node.id, 2);
+        }
+
+        let db = weighted_node_db(&[0, 0]);
+        assert!(db.query().execute().unwrap().any_weighted().is_none());
+
+        let db = weighted_node_db(&[1, 3]);
+        let result = db.query().execute().unwrap();
+        let mut counts = [0; 2];
+        for _ in 0..4000 {
+            counts[result.any_weighted().unwrap().id as usize] += 1;
+        }
+        // expected 1000 vs 3000
+        assert!(counts[0] > 700 && counts[0] < 1300, "{counts:?}");
+
+        // weights only apply to the rows found by the query
+        let mut db = weighted_node_db(&[10, 1]);
+        db.append(WeightedNode {
+            id: 2,
+            is_active: false,
+            capacity: 100,
+        })
+        .unwrap();
+        let mut query = db.query();
+        query.is_active(false);
+        for _ in 0..10 {
+            assert_eq!(query.execute().unwrap().any_weighted().unwrap().id, 2);
+        }
+
+        let result = db.query().execute().unwrap();
+        let result = result.filter(|node| node.capacity < 50).unwrap();
+        for _ in 0..100 {
+            assert_ne!(result.any_weighted().unwrap().id, 2);
+        }
+    }
+
+    #[test]
+    fn test_any_weighted_after_update() {
+        let mut db = weighted_node_db(&[5, 0, 0]);
+
+        // weights are kept in sync with the rows
+        db.update_by_id(&0, |node| node.capacity = 0).unwrap();
+        db.update_by_id(&1, |node| node.capacity = 3).unwrap();
+        for _ in 0..50 {
+            let node = db.query().execute().unwrap().any_weighted().unwrap();
+            assert_eq!(node.id, 1);
+        }
+
+        db.remove_by_id(&1);
+        assert!(db.query().execute().unwrap().any_weighted().is_none());
+
+        db.extend([
+            WeightedNode {
+                id: 3,
+                is_active: true,
+                capacity: 0,
+            },
+            WeightedNode {
+                id: 4,
+                is_active: true,
+                capacity: 2,
+            },
+        ])
+        .unwrap();
+        db.compact();
+        for _ in 0..50 {
+            let node = db.query().execute().unwrap().any_weighted().unwrap();
+            assert_eq!(node.id, 4);
+        }
+    }
+
+    #[test]
+    fn test_any_weighted_after_retain() {
+        let capacities: Vec<_> = (0..1000).map(|i| i % 7).collect();
+        let mut db = weighted_node_db(&capacities);
+
+        // only the rows with an id divisible by 100 and a capacity remain
+        db.retain(|node| node.id % 100 == 0 || node.capacity == 0);
+        db.remove_by_id(&0);
+        for seed in 0..50 {
+            let node = db
+                .query()
+                .execute()
+                .unwrap()
+                .any_weighted_seeded(seed)
+                .unwrap();
+            assert_eq!(node.id % 100, 0);
+            assert_ne!(node.capacity, 0);
+        }
+
+        db.retain(|node| node.capacity == 0);
+        assert!(db.query().execute().unwrap().any_weighted().is_none());
+    }
+
+    #[test]
+    fn test_any_with_injected_rng() {
+        let db = proxy_db();
+        let result = db.query().execute().unwrap();
+
+        // same seed, same selection
+        for seed in 0..20 {
+            assert_eq!(
+                result.any_seeded(seed).id,
+                result.any_with(&mut venndb::SeededRandom::new(seed)).id
+            );
+        }
+
+        let ids: std::collections::HashSet<_> =
+            (0..100).map(|seed| result.any_seeded(seed).id).collect();
+        assert_eq!(ids.len(), 4);
+
+        // a custom source of randomness
+        struct FixedRandom(u64);
+
+        impl venndb::RandomSource for FixedRandom {
+            fn random_u64(&mut self) -> u64 {
+                self.0
+            }
+        }
+
+        assert_eq!(result.any_with(&mut FixedRandom(0)).id, 1);
+        assert_eq!(result.any_with(&mut FixedRandom(u64::MAX)).id, 4);
+
+        let result = result.filter(|proxy| proxy.id > 2).unwrap();
+        assert_eq!(result.any_with(&mut FixedRandom(0)).id, 3);
+        assert_eq!(result.any_with(&mut FixedRandom(u64::MAX)).id, 4);
+    }
+
+    #[test]
+    fn test_any_weighted_seeded() {
+        let db = weighted_node_db(&[1, 0, 2, 7]);
+        let result = db.query().execute().unwrap();
+        for seed in 0..20 {
+            let node = result.any_weighted_seeded(seed).unwrap();
+            assert_ne!(node.id, 1);
+            assert_eq!(
+                node.id,
+                result
+                    .any_weighted_with(&mut venndb::SeededRandom::new(seed))
+                    .unwrap()
+                    .id
+            );
+        }
+    }
+
+    #[test]
+    fn test_sample() {
+        let db = proxy_db();
+        let result = db.query().execute().unwrap();
+
+        assert!(result.sample(0).is_empty());
+        let all: Vec<_> = result.sample(10).iter().map(|proxy| proxy.id).collect();
+        assert_eq!(all, vec![1, 2, 3, 4]);
+
+        let mut seen = std::collections::HashSet::new();
+        for seed in 0..50 {
+            let ids: Vec<_> = result
+                .sample_seeded(2, seed)
+                .iter()
+                .map(|proxy| proxy.id)
+                .collect();
+            assert_eq!(ids.len(), 2);
+            // distinct and in insertion order
+            assert!(ids[0] < ids[1]);
+            seen.insert(ids);
+        }
+        // all 6 combinations are possible
+        assert_eq!(seen.len(), 6);
+
+        let ids: Vec<_> = result.sample(3).iter().map(|proxy| proxy.id).collect();
+        assert_eq!(ids.len(), 3);
+
+        let result = result.filter(|proxy| proxy.id != 2).unwrap();
+        for seed in 0..20 {
+            let ids: Vec<_> = result
+                .sample_with(2, &mut venndb::SeededRandom::new(seed))
+                .iter()
+                .map(|proxy| proxy.id)
+                .collect();
+            assert_eq!(ids.len(), 2);
+            assert!(!ids.contains(&2));
+        }
+    }
+
+    #[test]
+    fn test_sample_uniform() {
+        let db = weighted_node_db(&[0; 200]);
+        let result = db.query().execute().unwrap();
+        let mut counts = [0; 200];
+        for seed in 0..1000 {
+            let nodes = result.sample_seeded(10, seed);
+            assert_eq!(nodes.len(), 10);
+            for node in nodes {
+                counts[node.id as usize] += 1;
+            }
+        }
+        // expected 50 per row
+        assert!(counts.iter().all(|count| *count > 15 && *count < 100));
+    }
+
+    #[test]
+    fn test_query_cursor() {
+        let mut db = proxy_db();
+        let mut cursor = ProxyDBQueryCursor::new();
+
+        let mut active = db.query();
+        active.is_active(true);
+        let mut mobile = db.query();
+        mobile.is_mobile(true);
+        let mut none = db.query();
+        none.is_active(false).is_mobile(true);
+
+        let ids: Vec<_> = (0..7)
+            .map(|_| cursor.next_row(&active).unwrap().id)
+            .collect();
+        assert_eq!(ids, vec![1, 2, 4, 1, 2, 4, 1]);
+
+        // each query has its own position
+        assert_eq!(cursor.next_row(&mobile).unwrap().id, 1);
+        assert_eq!(cursor.next_row(&mobile).unwrap().id, 1);
+        assert!(cursor.next_row(&none).is_none());
+
+        // the same filters share the same position
+        let mut active_again = db.query();
+        active_again.is_active(true);
+        assert_eq!(cursor.next_row(&active_again).unwrap().id, 2);
+        assert_eq!(cursor.next_row(&active).unwrap().id, 4);
+
+        cursor.reset();
+        assert_eq!(cursor.next_row(&active).unwrap().id, 1);
+
+        // positions survive mutations of the database
+        db.remove_by_id(&2);
+        let mut active = db.query();
+        active.is_active(true);
+        assert_eq!(cursor.next_row(&active).unwrap().id, 4);
+        assert_eq!(cursor.next_row(&active).unwrap().id, 1);
+
+        // queries only differing in their range bounds each have their own position
+        let db = server_db();
+        let mut cursor = ServerDBQueryCursor::new();
+        let mut fast = db.query();
+        fast.latency_lt(250u32);
+        let mut slow = db.query();
+        slow.latency_gt(150u32);
+        assert_eq!(cursor.next_row(&fast).unwrap().id, 2);
+        assert_eq!(cursor.next_row(&slow).unwrap().id, 1);
+        assert_eq!(cursor.next_row(&fast).unwrap().id, 3);
+        assert_eq!(cursor.next_row(&slow).unwrap().id, 3);
+        assert_eq!(cursor.next_row(&slow).unwrap().id, 1);
+        assert_eq!(cursor.next_row(&fast).unwrap().id, 4);
+    }
+
+    #[test]
+    fn test_facets() {
+        let mut db = proxy_db();
+
+        // any rows are counted for every value
+        assert_eq!(
+            db.facets_country(),
+            vec![(&Country::BE, 2), (&Country::Any, 1), (&Country::NL, 2)]
+        );
+
+        let mut query = db.query();
+        query.is_mobile(false);
+        let result = query.execute().unwrap();
+        assert_eq!(result.facets_country(), vec![(&Country::NL, 1)]);
+
+        let mut query = db.query();
+        query.is_active(true);
+        let result = query.execute().unwrap();
+        assert_eq!(
+            result.facets_country(),
+            vec![(&Country::BE, 2), (&Country::Any, 1), (&Country::NL, 2)]
+        );
+        let result = result.filter(|proxy| proxy.id != 2).unwrap();
+        assert_eq!(
+            result.facets_country(),
+            vec![(&Country::BE, 1), (&Country::NL, 1)]
+        );
+
+        // removed rows are not counted,
+        // the any row still matches the value of the removed row
+        db.remove_by_id(&1);
+        assert_eq!(
+            db.facets_country(),
+            vec![(&Country::BE, 1), (&Country::Any, 1), (&Country::NL, 2)]
+        );
+        db.remove_by_id(&2);
+        assert_eq!(db.facets_country(), vec![(&Country::NL, 1)]);
+    }
+
+    #[test]
+    fn test_distinct() {
+        let mut db = proxy_db();
+
+        assert_eq!(
+            db.distinct_country().collect::<Vec<_>>(),
+            vec![&Country::BE, &Country::NL]
+        );
+        assert!(db.has_any_country());
+
+        db.remove_by_id(&2);
+        assert!(!db.has_any_country());
+
+        db.update_by_id(&1, |proxy| proxy.country = Some(Country::US))
+            .unwrap();
+        assert_eq!(
+            db.distinct_country().collect::<Vec<_>>(),
+            vec![&Country::NL, &Country::US]
+        );
+
+        db.remove_by_id(&4);
+        assert_eq!(
+            db.distinct_country().collect::<Vec<_>>(),
+            vec![&Country::US]
+        );
+
+        let db = employee_db();
+        assert_eq!(
+            db.distinct_department().collect::<Vec<_>>(),
+            vec![
+                &Department::Engineering,
+                &Department::HR,
+                &Department::Sales,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_by() {
+        let db = proxy_db();
+
+        let mut query = db.query();
+        query.is_active(true);
+        let groups: Vec<_> = query
+            .execute()
+            .unwrap()
+            .group_by_country()
+            .into_iter()
+            .map(|(country, result)| {
+                (
+                    country.clone(),
+                    result.iter().map(|proxy| proxy.id).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        // any rows are part of every group
+        assert_eq!(
+            groups,
+            vec![
+                (Country::BE, vec![1, 2]),
+                (Country::Any, vec![2]),
+                (Country::NL, vec![2, 4]),
+            ]
+        );
+
+        // one random proxy per country
+        let result = db.query().execute().unwrap();
+        let result = result.filter(|proxy| proxy.id != 2).unwrap();
+        let picks: Vec<_> = result
+            .group_by_country()
+            .iter()
+            .map(|(country, result)| ((*country).clone(), result.any().id))
+            .collect();
+        assert_eq!(picks, vec![(Country::BE, 1), (Country::NL, 4)]);
+
+        let mut query = db.query();
+        query.country_is_none();
+        assert!(query.execute().unwrap().group_by_country().is_empty());
+    }
+
+    #[test]
+    fn test_page() {
+        let db = weighted_node_db(&[0; 150]);
+        let result = db.query().execute().unwrap();
+
+        let ids = |iter: &mut dyn Iterator<Item = &WeightedNode>| -> Vec<u32> {
+            iter.map(|node| node.id).collect()
+        };
+
+        assert_eq!(ids(&mut result.page(0, 3)), vec![0, 1, 2]);
+        assert_eq!(ids(&mut result.page(70, 2)), vec![70, 71]);
+        assert_eq!(ids(&mut result.page(148, 10)), vec![148, 149]);
+        assert!(ids(&mut result.page(150, 10)).is_empty());
+        assert!(ids(&mut result.page(10, 0)).is_empty());
+
+        let result = result.filter(|node| node.id % 2 == 1).unwrap();
+        assert_eq!(ids(&mut result.page(0, 2)), vec![1, 3]);
+        assert_eq!(ids(&mut result.page(70, 10)), vec![141, 143, 145, 147, 149]);
+        assert!(ids(&mut result.page(75, 1)).is_empty());
+
+        // skipping is also efficient using the regular iterator
+        assert_eq!(result.iter().nth(3).unwrap().id, 7);
+        assert_eq!(ids(&mut result.iter().skip(73)), vec![147, 149]);
+
+        let mut db = proxy_db();
+        db.remove_by_id(&2);
+        let result = db.query().execute().unwrap();
+        assert_eq!(
+            result.page(1, 5).map(|proxy| proxy.id).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+    }
+
+    #[test]
+    fn test_sorted_by() {
+        let mut db = server_db();
+
+        // equal values are kept in insertion order
+        assert_eq!(server_ids(db.sorted_by_latency()), vec![2, 4, 3, 1]);
+        assert_eq!(server_ids(db.sorted_by_latency().rev()), vec![1, 3, 4, 2]);
+        // none values come first
+        assert_eq!(server_ids(db.sorted_by_priority()), vec![2, 3, 1, 4]);
+
+        let mut query = db.query();
+        query.is_active(true);
+        let result = query.execute().unwrap();
+        assert_eq!(server_ids(result.sorted_by_latency()), vec![4, 3, 1]);
+        let result = result.filter(|server| server.id != 4).unwrap();
+        assert_eq!(server_ids(result.sorted_by_latency()), vec![3, 1]);
+
+        db.append(Server {
+            id: 5,
+            is_active: true,
+            latency: 150,
+            priority: Some(1),
+        })
+        .unwrap();
+        db.extend([
+            Server {
+                id: 6,
+                is_active: false,
+                latency: 100,
+                priority: None,
+            },
+            Server {
+                id: 7,
+                is_active: true,
+                latency: 400,
+                priority: Some(0),
+            },
+        ])
+        .unwrap();
+        assert_eq!(
+            server_ids(db.sorted_by_latency()),
+            vec![2, 4, 6, 5, 3, 1, 7]
+        );
+        assert_eq!(
+            server_ids(db.sorted_by_priority()),
+            vec![2, 6, 7, 3, 5, 1, 4]
+        );
+
+        db.update_by_id(&4, |server| server.latency = 250).unwrap();
+        db.upsert(Server {
+            id: 2,
+            is_active: false,
+            latency: 500,
+            priority: None,
+        })
+        .unwrap();
+        assert_eq!(
+            server_ids(db.sorted_by_latency()),
+            vec![6, 5, 3, 4, 1, 7, 2]
+        );
+
+        db.remove_by_id(&5);
+        assert_eq!(server_ids(db.sorted_by_latency()), vec![6, 3, 4, 1, 7, 2]);
+        db.compact();
+        assert_eq!(server_ids(db.sorted_by_latency()), vec![6, 3, 4, 1, 7, 2]);
+        assert_eq!(server_ids(db.sorted_by_priority()), vec![2, 6, 7, 3, 1, 4]);
+
+        let mut query = db.query();
+        query.is_active(true);
+        let result = query.execute().unwrap();
+        assert_eq!(server_ids(result.sorted_by_latency()), vec![3, 4, 1, 7]);
+
+        // rows extended before a rejected row are sorted as well
+        assert!(
+            db.extend([
+                Server {
+                    id: 8,
+                    is_active: true,
+                    latency: 50,
+                    priority: Some(3),
+                },
+                Server {
+                    id: 1,
+                    is_active: true,
+                    latency: 10,
+                    priority: None,
+                },
+            ])
+            .is_err()
+        );
+        assert_eq!(
+            server_ids(db.sorted_by_latency()),
+            vec![8, 6, 3, 4, 1, 7, 2]
+        );
+        assert_eq!(
+            server_ids(db.sorted_by_priority()),
+            vec![2, 6, 7, 3, 1, 4, 8]
+        );
+    }
+
+    #[test]
+    fn test_range() {
+        let mut db = server_db();
+
+        let mut query = db.query();
+        query.latency_between(100u32, 200u32);
+        let mut ids = server_ids(query.execute().unwrap().iter());
+        ids.sort_unstable();
+        assert_eq!(ids, vec![2, 3, 4]);
+
+        // bounds combine with each other and with the other filters
+        query.reset().latency_gt(100u32).latency_lt(300u32);
+        assert_eq!(server_ids(query.execute().unwrap().iter()), vec![3]);
+        query.reset().latency_lt(300u32).is_active(true);
+        let mut ids = server_ids(query.execute().unwrap().iter());
+        ids.sort_unstable();
+        assert_eq!(ids, vec![3, 4]);
+        assert!(query.reset().latency_gt(300u32).execute().is_none());
+        assert!(
+            query
+                .reset()
+                .latency_between(300u32, 100u32)
+                .execute()
+                .is_none()
+        );
+
+        // rows without a value are never in range
+        query.reset().priority_lt(3u8);
+        let mut ids = server_ids(query.execute().unwrap().iter());
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 3, 4]);
+        query.reset().priority_between(0u8, 1u8);
+        assert_eq!(server_ids(query.execute().unwrap().iter()), vec![3]);
+
+        // ranges follow updates and removals
+        db.update_by_id(&1, |server| server.latency = 50).unwrap();
+        db.remove_by_id(&2);
+        let mut query = db.query();
+        query.latency_lt(150u32);
+        let mut ids = server_ids(query.execute().unwrap().iter());
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 4]);
+        db.compact();
+        let mut query = db.query();
+        query.latency_lt(150u32);
+        assert_eq!(query.count(), 2);
+    }
+
+    #[test]
+    fn test_range_many_rows() {
+        // enough rows to be divided over multiple bins of the range index
+        let server = |id: u32| Server {
+            id,
+            is_active: id.is_multiple_of(2),
+            latency: (id * 7919) % 1000,
+            priority: (!id.is_multiple_of(5)).then_some((id % 200) as u8),
+        };
+        let mut db = ServerDB::from_rows((0..3000).map(server).collect()).unwrap();
+        db.extend((3000..6000).map(server)).unwrap();
+        for id in 6000..7000 {
+            db.append(server(id)).unwrap();
+        }
+        for id in (0..7000).step_by(3) {
+            db.update_by_id(&id, |server| server.latency = 999 - server.latency)
+                .unwrap();
+        }
+        for id in (0..7000).step_by(11) {
+            db.remove_by_id(&id);
+        }
+
+        fn assert_latency_between(db: &ServerDB, low: u32, high: u32) {
+            let mut query = db.query();
+            query.latency_between(low, high).is_active(true);
+            let mut ids = query
+                .execute()
+                .map(|result| server_ids(result.iter()))
+                .unwrap_or_default();
+            ids.sort_unstable();
+            let expected: Vec<_> = db
+                .iter()
+                .filter(|server| server.is_active && (low..=high).contains(&server.latency))
+                .map(|server| server.id)
+                .collect();
+            assert_eq!(ids, expected);
+        }
+
+        for (low, high) in [(0, 0), (0, 999), (100, 250), (500, 501), (990, 2000)] {
+            assert_latency_between(&db, low, high);
+        }
+        let mut query = db.query();
+        query.priority_gt(100u8);
+        assert_eq!(
+            query.count(),
+            db.iter()
+                .filter(|server| server.priority.is_some_and(|priority| priority > 100))
+                .count()
+        );
+
+        db.compact();
+        for (low, high) in [(0, 10), (100, 250), (999, 999)] {
+            assert_latency_between(&db, low, high);
+        }
+    }
+
+    #[test]
+    fn test_get_all_by() {
+        let mut db = RouteDB::from_rows(vec![
+            route(1, "a.example", Some("eu")),
+            route(2, "b.example", None),
+            route(3, "a.example", Some("us")),
+        ])
+        .unwrap();
+
+        assert_eq!(route_ids(db.get_all_by_upstream("a.example")), vec![1, 3]);
+        assert_eq!(db.get_all_by_upstream("b.example").len(), 1);
+        assert_eq!(db.get_all_by_upstream("c.example").count(), 0);
+        assert_eq!(route_ids(db.get_all_by_region("eu")), vec![1]);
+
+        db.append(route(4, "a.example", Some("eu"))).unwrap();
+        assert_eq!(
+            route_ids(db.get_all_by_upstream("a.example")),
+            vec![1, 3, 4]
+        );
+        assert_eq!(route_ids(db.get_all_by_region("eu").rev()), vec![4, 1]);
+
+        // updated rows move to their new value, keeping the insertion order
+        db.update_by_id(&4, |route| route.upstream = "b.example".to_owned())
+            .unwrap();
+        db.update_by_id(&2, |route| route.region = Some("eu".to_owned()))
+            .unwrap();
+        db.update_by_id(&1, |route| route.region = None).unwrap();
+        assert_eq!(route_ids(db.get_all_by_upstream("a.example")), vec![1, 3]);
+        assert_eq!(route_ids(db.get_all_by_upstream("b.example")), vec![2, 4]);
+        assert_eq!(route_ids(db.get_all_by_region("eu")), vec![2, 4]);
+
+        db.upsert(route(3, "c.example", Some("us"))).unwrap();
+        assert_eq!(route_ids(db.get_all_by_upstream("a.example")), vec![1]);
+        assert_eq!(route_ids(db.get_all_by_upstream("c.example")), vec![3]);
+
+        db.remove_by_id(&1);
+        assert_eq!(db.get_all_by_upstream("a.example").count(), 0);
+        db.remove_by_id(&2);
+        db.compact();
+        assert_eq!(route_ids(db.get_all_by_upstream("b.example")), vec![4]);
+        assert_eq!(route_ids(db.get_all_by_region("eu")), vec![4]);
+        assert_eq!(route_ids(db.get_all_by_region("us")), vec![3]);
+    }
+
+    #[test]
+    fn test_composite_key() {
+        let err = EndpointDB::from_rows(vec![
+            endpoint(1, "a.example", 443),
+            endpoint(2, "a.example", 443),
+        ])
+        .unwrap_err();
+        assert_eq!(EndpointDBErrorKind::DuplicateKey, err.kind());
+
+        let mut db = EndpointDB::from_rows(vec![
+            endpoint(1, "a.example", 443),
+            endpoint(2, "a.example", 80),
+            endpoint(3, "b.example", 443),
+        ])
+        .unwrap();
+
+        assert_eq!(db.get_by_host_port("a.example", &443).unwrap().id, 1);
+        assert_eq!(db.get_by_host_port("a.example", &80).unwrap().id, 2);
+        assert!(db.get_by_host_port("b.example", &80).is_none());
+
+        let err = db.append(endpoint(4, "b.example", 443)).unwrap_err();
+        assert_eq!(EndpointDBErrorKind::DuplicateKey, err.kind());
+        db.append(endpoint(4, "b.example", 80)).unwrap();
+        assert_eq!(db.get_by_host_port("b.example", &80).unwrap().id, 4);
+
+        // only the combination of the fields has to be unique
+        db.update_by_host_port("b.example", &80, |endpoint| endpoint.port = 8080)
+            .unwrap();
+        assert!(db.get_by_host_port("b.example", &80).is_none());
+        assert_eq!(db.get_by_host_port("b.example", &8080).unwrap().id, 4);
+        let err = db
+            .update_by_id(&4, |endpoint| endpoint.port = 443)
+            .unwrap_err();
+        assert_eq!(EndpointDBErrorKind::DuplicateKey, err.kind());
+        assert_eq!(db.get_by_id(&4).unwrap().port, 8080);
+
+        *db.get_mut_by_host_port("a.example", &80).unwrap().requests += 1;
+        assert_eq!(db.get_by_id(&2).unwrap().requests, 1);
+
+        // upserts look up the existing row by all keys
+        let previous = db.upsert(endpoint(2, "c.example", 80)).unwrap().unwrap();
+        assert_eq!(previous.host, "a.example");
+        assert!(db.get_by_host_port("a.example", &80).is_none());
+        let err = db.upsert(endpoint(3, "a.example", 443)).unwrap_err();
+        assert_eq!(EndpointDBErrorKind::DuplicateKey, err.kind());
+
+        assert!(db.remove_by_host_port("a.example", &443));
+        assert!(db.get_by_id(&1).is_none());
+        db.append(endpoint(1, "a.example", 443)).unwrap();
+        db.compact();
+        assert_eq!(db.len(), 4);
+        assert_eq!(db.get_by_host_port("a.example", &443).unwrap().id, 1);
+        assert_eq!(db.get_by_host_port("c.example", &80).unwrap().id, 2);
+        assert_eq!(db.get_by_host_port("b.example", &8080).unwrap().id, 4);
+    }
+
+    #[test]
+    fn test_optional_key() {
+        // rows without a key value are not registered for that key
+        let mut db = AccountDB::from_rows(vec![
+            account(1, Some("a")),
+            account(2, None),
+            account(3, None),
+        ])
+        .unwrap();
+        assert_eq!(db.get_by_external_id("a").unwrap().id, 1);
+        assert!(db.get_by_external_id("b").is_none());
+
+        // values which are defined still have to be unique
+        let err = db.append(account(4, Some("a"))).unwrap_err();
+        assert_eq!(AccountDBErrorKind::DuplicateKey, err.kind());
+        db.append(account(4, None)).unwrap();
+        assert_eq!(db.len(), 4);
+
+        // a key value can be assigned later
+        db.update_by_id(&2, |account| account.external_id = Some("b".to_owned()))
+            .unwrap();
+        assert_eq!(db.get_by_external_id("b").unwrap().id, 2);
+        let err = db
+            .update_by_id(&3, |account| account.external_id = Some("b".to_owned()))
+            .unwrap_err();
+        assert_eq!(AccountDBErrorKind::DuplicateKey, err.kind());
+        db.update_by_id(&1, |account| account.external_id = None)
+            .unwrap();
+        assert!(db.get_by_external_id("a").is_none());
+
+        // upserts only match on defined key values
+        let previous = db.upsert(account(3, Some("a"))).unwrap().unwrap();
+        assert_eq!(previous.external_id, None);
+        assert_eq!(db.get_by_external_id("a").unwrap().id, 3);
+        assert!(db.upsert(account(5, None)).unwrap().is_none());
+        assert_eq!(db.len(), 5);
+
+        assert!(db.remove_by_external_id("b"));
+        db.remove_by_id(&1);
+        db.compact();
+        assert_eq!(db.len(), 3);
+        assert!(db.get_by_external_id("b").is_none());
+        assert_eq!(db.get_by_external_id("a").unwrap().id, 3);
+        db.append(account(6, Some("b"))).unwrap();
+        assert_eq!(db.get_by_external_id("b").unwrap().id, 6);
+    }
+
+    #[test]
+    fn test_multi_valued_filter_map() {
+        let mut db = GatewayDB::from_rows(vec![
+            gateway(1, &["http", "socks5"], &["eu", "fast"]),
+            gateway(2, &["http"], &["us"]),
+            gateway(3, &["socks5", "https"], &["eu"]),
+            gateway(4, &[], &[]),
+        ])
+        .unwrap();
+
+        // rows match any of their values, and any of the given values
+        let mut query = db.query();
+        query.protocols("http");
+        assert_eq!(gateway_ids(&query), vec![1, 2]);
+        query.protocols("https");
+        assert_eq!(gateway_ids(&query), vec![1, 2, 3]);
+
+        // or have to match all of the given values
+        query
+            .reset()
+            .all_of_protocols("http")
+            .all_of_protocols("socks5");
+        assert_eq!(gateway_ids(&query), vec![1]);
+        query.reset().all_of_labels("eu");
+        assert_eq!(gateway_ids(&query), vec![1, 3]);
+        query.all_of_labels("slow");
+        assert!(query.execute().is_none());
+
+        query.reset().not_protocols("http");
+        assert_eq!(gateway_ids(&query), vec![3, 4]);
+        query.reset().protocols("socks5").not_labels("fast");
+        assert_eq!(gateway_ids(&query), vec![3]);
+
+        let mut query = db.query();
+        query.expr(GatewayDBExpr::or(
+            GatewayDBExpr::field_protocols("https"),
+            GatewayDBExpr::field_labels("us"),
+        ));
+        assert_eq!(gateway_ids(&query), vec![2, 3]);
+
+        let mut facets = db.facets_protocols();
+        facets.sort();
+        assert_eq!(
+            facets,
+            vec![
+                (&"http".to_owned(), 2),
+                (&"https".to_owned(), 1),
+                (&"socks5".to_owned(), 2)
+            ]
+        );
+
+        // values are re-registered when updated or appended
+        db.update_by_id(&2, |gateway| gateway.protocols.push("socks5".to_owned()))
+            .unwrap();
+        db.append(gateway(5, &["quic"], &["eu", "us"])).unwrap();
+        let mut query = db.query();
+        query.all_of_protocols("socks5");
+        assert_eq!(gateway_ids(&query), vec![1, 2, 3]);
+        query.reset().all_of_labels("eu").all_of_labels("us");
+        assert_eq!(gateway_ids(&query), vec![5]);
+        query.reset().protocols("quic");
+        assert_eq!(gateway_ids(&query), vec![5]);
+
+        db.update_by_id(&3, |gateway| gateway.protocols.clear())
+            .unwrap();
+        db.remove_by_id(&5);
+        let stats = db.compact();
+        assert_eq!(stats.filter_values_removed, 2);
+        let mut distinct: Vec<_> = db.distinct_protocols().collect();
+        distinct.sort();
+        assert_eq!(distinct, vec!["http", "socks5"]);
+    }
+
+    #[test]
+    fn test_collection_filter_map_whole_value() {
+        let db = ChainDB::from_rows(vec![
+            chain(1, &["a", "b"]),
+            chain(2, &["a"]),
+            chain(3, &["b", "a"]),
+            chain(4, &["a", "b"]),
+        ])
+        .unwrap();
+
+        let hops =
+            |hops: &[&str]| -> Vec<String> { hops.iter().map(|hop| hop.to_string()).collect() };
+        let chain_ids = |query: &ChainDBQuery| -> Vec<u32> {
+            query
+                .execute()
+                .map(|result| result.iter().map(|chain| chain.id).collect())
+                .unwrap_or_default()
+        };
+
+        // rows only match the collection as a whole
+        let mut query = db.query();
+        query.hops(hops(&["a", "b"]));
+        assert_eq!(chain_ids(&query), vec![1, 4]);
+        query.reset().hops(hops(&["a"]));
+        assert_eq!(chain_ids(&query), vec![2]);
+        query.reset().not_hops(hops(&["a"]));
+        assert_eq!(chain_ids(&query), vec![1, 3, 4]);
+        query.reset().hops(hops(&["b"]));
+        assert!(query.execute().is_none());
+
+        assert_eq!(db.distinct_hops().count(), 3);
+    }
+
+    #[test]
+    fn test_computed_filters() {
+        let mut db = PeerDB::from_rows(vec![
+            Peer {
+                id: 1,
+                latency: 50,
+                tier: Some(3),
+            },
+            Peer {
+                id: 2,
+                latency: 250,
+                tier: Some(1),
+            },
+            Peer {
+                id: 3,
+                latency: 800,
+                tier: None,
+            },
+            Peer {
+                id: 4,
+                latency: 20,
+                tier: None,
+            },
+        ])
+        .unwrap();
+
+        let mut query = db.query();
+        query.is_fast(true);
+        assert_eq!(peer_ids(&query), vec![1, 4]);
+        query.reset().is_fast(false);
+        assert_eq!(peer_ids(&query), vec![2, 3]);
+
+        query.reset().latency_bucket(LatencyBucket::Medium);
+        assert_eq!(peer_ids(&query), vec![2]);
+        query.latency_bucket(LatencyBucket::High);
+        assert_eq!(peer_ids(&query), vec![2, 3]);
+
+        query.reset().is_premium(false);
+        assert_eq!(peer_ids(&query), vec![2]);
+        query.reset().is_premium_is_none();
+        assert_eq!(peer_ids(&query), vec![3, 4]);
+
+        let mut facets = db.facets_latency_bucket();
+        facets.sort_by_key(|(bucket, _)| format!("{bucket:?}"));
+        assert_eq!(
+            facets,
+            vec![
+                (&LatencyBucket::High, 1),
+                (&LatencyBucket::Low, 2),
+                (&LatencyBucket::Medium, 1)
+            ]
+        );
+
+        // computed filters are derived again when the row is updated or appended
+        db.update_by_id(&3, |peer| {
+            peer.latency = 10;
+            peer.tier = Some(5);
+        })
+        .unwrap();
+        db.append(Peer {
+            id: 5,
+            latency: 600,
+            tier: Some(4),
+        })
+        .unwrap();
+        let mut query = db.query();
+        query.is_fast(true).is_premium(true);
+        assert_eq!(peer_ids(&query), vec![1, 3]);
+        query.reset().latency_bucket(LatencyBucket::High);
+        assert_eq!(peer_ids(&query), vec![5]);
+    }
+}
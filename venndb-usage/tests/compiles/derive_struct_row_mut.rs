@@ -0,0 +1,35 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Employee {
+    #[venndb(key)]
+    id: u32,
+    name: String,
+    is_manager: bool,
+    #[venndb(skip)]
+    is_active: bool,
+}
+
+fn main() {
+    let mut db = EmployeeDB::new();
+    db.append(Employee {
+        id: 1,
+        name: "Alice".to_string(),
+        is_manager: false,
+        is_active: false,
+    })
+    .unwrap();
+
+    let employee = db.get_mut_by_id(&1).unwrap();
+    assert!(!*employee.is_manager);
+    *employee.name = "Bob".to_string();
+    *employee.is_active = true;
+
+    for employee in db.iter_mut_unindexed() {
+        employee.name.push_str(" Smith");
+    }
+
+    let employee = db.get_by_id(&1).unwrap();
+    assert_eq!(employee.name, "Bob Smith");
+    assert!(employee.is_active);
+}
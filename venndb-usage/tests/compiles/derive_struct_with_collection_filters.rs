@@ -0,0 +1,34 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Gateway {
+    #[venndb(key)]
+    id: u32,
+    // the collection as a whole is a single filter map value
+    #[venndb(filter)]
+    route: Vec<String>,
+    // each value of the collection is a filter map value
+    #[venndb(filter(multi))]
+    ports: std::collections::BTreeSet<u16>,
+}
+
+fn main() {
+    let mut db = GatewayDB::new();
+    db.append(Gateway {
+        id: 1,
+        route: vec!["eu".to_owned(), "be".to_owned()],
+        ports: [80, 443].into(),
+    })
+    .unwrap();
+
+    let mut query = db.query();
+    query
+        .route(vec!["eu".to_owned(), "be".to_owned()])
+        .ports(443u16)
+        .all_of_ports(80u16);
+    assert_eq!(query.execute().unwrap().first().id, 1);
+
+    query.reset();
+    query.route(vec!["eu".to_owned()]);
+    assert!(query.execute().is_none());
+}
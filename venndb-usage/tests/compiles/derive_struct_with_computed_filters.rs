@@ -0,0 +1,42 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+#[venndb(filter(name = "is_fast", with = node_is_fast))]
+#[venndb(filter(name = "region", with = node_region, ty = Option<String>))]
+#[venndb(filter(name = "ports", with = node_ports, ty = Vec<u16>, multi))]
+struct Node {
+    #[venndb(key)]
+    id: u32,
+    latency: u32,
+    address: String,
+}
+
+fn node_is_fast(node: &Node) -> bool {
+    node.latency < 100
+}
+
+fn node_region(node: &Node) -> Option<String> {
+    node.address.split_once('.').map(|(region, _)| region.to_owned())
+}
+
+fn node_ports(_node: &Node) -> Vec<u16> {
+    vec![80, 443]
+}
+
+fn main() {
+    let mut db = NodeDB::new();
+    db.append(Node {
+        id: 1,
+        latency: 50,
+        address: "eu.example.com".to_owned(),
+    })
+    .unwrap();
+
+    let mut query = db.query();
+    query
+        .is_fast(true)
+        .region("eu")
+        .all_of_ports(80u16)
+        .all_of_ports(443u16);
+    assert_eq!(query.execute().unwrap().any().id, 1);
+}
@@ -0,0 +1,12 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Employee {
+    #[venndb(key)]
+    id: Option<u32>,
+}
+
+fn main() {
+    let db = EmployeeDB::from_iter([Employee { id: Some(1) }, Employee { id: None }]).unwrap();
+    assert_eq!(db.get_by_id(&1).unwrap().id, Some(1));
+}
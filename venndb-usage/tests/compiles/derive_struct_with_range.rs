@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Session {
+    #[venndb(range)]
+    age: Duration,
+    #[venndb(range)]
+    score: Option<i64>,
+}
+
+fn main() {
+    let db = SessionDB::from_iter([Session {
+        age: Duration::from_secs(5),
+        score: Some(-3),
+    }]);
+    let mut query = db.query();
+    query
+        .age_between(Duration::from_secs(1), Duration::from_secs(10))
+        .score_lt(0);
+    assert_eq!(query.count(), 1);
+}
@@ -0,0 +1,17 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Node {
+    is_active: bool,
+    #[venndb(weight)]
+    capacity: Option<f32>,
+}
+
+fn main() {
+    let db = NodeDB::from_iter([Node {
+        is_active: true,
+        capacity: Some(1.5),
+    }]);
+    let node = db.query().execute().unwrap().any_weighted().unwrap();
+    assert_eq!(node.capacity, Some(1.5));
+}
@@ -0,0 +1,9 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Proxy {
+    #[venndb(filter(multi), any)]
+    protocols: Vec<String>,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+#[venndb(clone)]
+struct Employee {
+    #[venndb(key)]
+    id: u32,
+    is_manager: bool,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+#[venndb(key(host, port))]
+#[venndb(key(host_port, scheme))]
+#[venndb(key(host, port_scheme))]
+struct Endpoint {
+    host: String,
+    port: u16,
+    scheme: String,
+    host_port: String,
+    port_scheme: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+#[venndb(key(host))]
+struct Endpoint {
+    host: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+#[venndb(key(host, port))]
+struct Endpoint {
+    host: String,
+}
+
+fn main() {}
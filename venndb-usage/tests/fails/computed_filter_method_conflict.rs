@@ -0,0 +1,19 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+#[venndb(key(host, port))]
+#[venndb(filter(name = "host_port", with = node_is_fast))]
+#[venndb(filter(name = "is_up_is_none", with = node_is_fast))]
+#[venndb(filter(name = "reset", with = node_is_fast))]
+struct Node {
+    host: String,
+    port: u16,
+    is_up: Option<bool>,
+    latency: u32,
+}
+
+fn node_is_fast(node: &Node) -> bool {
+    node.latency < 100
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+#[venndb(filter(name = "is_fast", with = node_is_fast))]
+struct Node {
+    latency: u32,
+    is_fast: bool,
+}
+
+fn node_is_fast(node: &Node) -> bool {
+    node.latency < 100
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Counter {
+    #[venndb(key)]
+    id: u32,
+    count: bool,
+    exists: bool,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Rule {
+    #[venndb(key)]
+    id: u32,
+    expr: bool,
+}
+
+fn main() {}
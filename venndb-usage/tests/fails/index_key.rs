@@ -2,8 +2,8 @@ use venndb::VennDB;
 
 #[derive(Debug, VennDB)]
 struct Employee {
-    #[venndb(key)]
-    id: Option<u32>,
+    #[venndb(key, index)]
+    id: u32,
 }
 
 fn main() {}
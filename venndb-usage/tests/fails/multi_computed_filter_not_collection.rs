@@ -0,0 +1,13 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+#[venndb(filter(name = "region", with = node_region, ty = String, multi))]
+struct Node {
+    address: String,
+}
+
+fn node_region(node: &Node) -> String {
+    node.address.clone()
+}
+
+fn main() {}
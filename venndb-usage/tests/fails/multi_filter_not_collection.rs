@@ -0,0 +1,9 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Gateway {
+    #[venndb(filter(multi))]
+    protocol: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Gateway {
+    #[venndb(filter(many))]
+    protocols: Vec<String>,
+}
+
+fn main() {}
@@ -0,0 +1,22 @@
+use venndb::VennDB;
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+struct Latency(f64);
+
+impl Eq for Latency {}
+
+impl Ord for Latency {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+#[derive(Debug, VennDB)]
+struct Server {
+    #[venndb(key)]
+    id: u32,
+    #[venndb(range)]
+    latency: Latency,
+}
+
+fn main() {}
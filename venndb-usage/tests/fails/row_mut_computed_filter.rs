@@ -0,0 +1,21 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+#[venndb(filter(name = "is_fast", with = node_is_fast))]
+struct Node {
+    #[venndb(key)]
+    id: u32,
+    latency: u32,
+}
+
+fn node_is_fast(node: &Node) -> bool {
+    node.latency < 100
+}
+
+fn main() {
+    let mut db = NodeDB::new();
+    db.append(Node { id: 1, latency: 50 }).unwrap();
+
+    let node = db.get_mut_by_id(&1).unwrap();
+    *node.latency = 200;
+}
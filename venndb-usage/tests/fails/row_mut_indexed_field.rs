@@ -0,0 +1,23 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Employee {
+    #[venndb(key)]
+    id: u32,
+    name: String,
+    is_manager: bool,
+}
+
+fn main() {
+    let mut db = EmployeeDB::new();
+    db.append(Employee {
+        id: 1,
+        name: "Alice".to_string(),
+        is_manager: false,
+    })
+    .unwrap();
+
+    let employee = db.get_mut_by_id(&1).unwrap();
+    *employee.name = "Bob".to_string();
+    *employee.is_manager = true;
+}
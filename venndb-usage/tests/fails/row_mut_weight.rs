@@ -0,0 +1,17 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Node {
+    #[venndb(key)]
+    id: u32,
+    #[venndb(weight)]
+    capacity: u32,
+}
+
+fn main() {
+    let mut db = NodeDB::new();
+    db.append(Node { id: 1, capacity: 5 }).unwrap();
+
+    let node = db.get_mut_by_id(&1).unwrap();
+    *node.capacity = 10;
+}
@@ -0,0 +1,10 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Server {
+    #[venndb(key, sort)]
+    id: u32,
+    is_active: bool,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Node {
+    is_active: bool,
+    #[venndb(weight, filter)]
+    capacity: u32,
+}
+
+fn main() {}
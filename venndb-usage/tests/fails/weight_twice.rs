@@ -0,0 +1,12 @@
+use venndb::VennDB;
+
+#[derive(Debug, VennDB)]
+struct Node {
+    is_active: bool,
+    #[venndb(weight)]
+    capacity: u32,
+    #[venndb(weight)]
+    priority: u32,
+}
+
+fn main() {}